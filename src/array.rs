@@ -1,12 +1,15 @@
 //! Types for arrays of nibbles.
-use core::ops;
+use core::ops::{self, RangeBounds};
 use core::slice::{self as stdslice, from_raw_parts, from_raw_parts_mut};
+use core::convert::TryFrom;
+use core::fmt;
 use core::iter::FromIterator;
 use arrayvec::{Array, ArrayVec, CapacityError};
 use base::{u4lo, u4};
 use pair::u4x2;
 use slice::{self, NibSliceAligned, NibSliceAlignedMut, NibSliceFull, NibSliceNoR};
-use common::{get_nib, shift_left, shift_right, set_nib};
+use common::{copy_nibs, fill_nibs, get_nib, move_nibs, nib_range, shift_left, shift_right, set_nib};
+use slice::{NibSlice, NibSliceExt, NibSliceMutExt};
 
 /// An `ArrayVec` of nibbles.
 #[derive(Clone)]
@@ -22,17 +25,17 @@ impl<A: Array<Item = u4x2>> NibArrayVec<A> {
 
     /// Number of nibbles in the vector.
     pub fn len(&self) -> usize {
-        (self.inner.len() >> 1).saturating_sub(!self.has_right_lo as usize)
+        (self.inner.len() << 1) - !self.has_right_lo as usize
     }
 
     /// Whether the vector is empty.
     pub fn is_empty(&self) -> bool {
-        self.inner.is_empty()
+        self.len() == 0
     }
 
     /// How many nibbles can be stored in the vector.
     pub fn capacity(&self) -> usize {
-        self.inner.capacity() >> 1
+        self.inner.capacity() << 1
     }
 
     /// Whether the vector is full.
@@ -40,19 +43,41 @@ impl<A: Array<Item = u4x2>> NibArrayVec<A> {
         self.inner.is_full() && (self.inner.capacity() == 0 || self.has_right_lo)
     }
 
+    /// Creates a vector holding a copy of the nibbles in a slice.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the nibbles don't fit in the vector.
+    pub fn from_nibs<S: ?Sized + NibSliceExt>(nibs: &S) -> Self {
+        let mut vec = Self::new();
+        vec.extend_from_nibs(nibs);
+        vec
+    }
+
+    /// Copies all the nibbles from a slice onto the end of this vector, if they fit.
+    ///
+    /// If they don't fit, the vector is left unchanged.
+    pub fn try_extend_from_nibs<S: ?Sized + NibSliceExt>(&mut self, nibs: &S) -> Result<(), CapacityError<()>> {
+        if self.len() + nibs.len() > self.capacity() {
+            return Err(CapacityError::new(()));
+        }
+        self.extend_from_nibs(nibs);
+        Ok(())
+    }
+
     /// Pushes a nibble onto the vector.
     ///
     /// # Panics
     ///
     /// Panics if the vector is full.
     pub fn push<T: u4>(&mut self, nib: T) {
-        self.has_right_lo = !self.has_right_lo;
         if self.has_right_lo {
             self.inner.push(u4x2::from_hi(nib.to_u4hi()));
         } else {
             let i = self.inner.len() - 1;
             self.inner[i].set_lo(nib)
         }
+        self.has_right_lo = !self.has_right_lo;
     }
 
     /// Pushes a nibble onto the vector if possible.
@@ -65,41 +90,207 @@ impl<A: Array<Item = u4x2>> NibArrayVec<A> {
         } else {
             let i = self.inner.len() - 1;
             self.inner[i].set_lo(nib);
+            self.has_right_lo = true;
         }
         Ok(())
     }
 
     /// Pushes a nibble onto the vector without checking if it's full.
     pub unsafe fn push_unchecked<T: u4>(&mut self, nib: T) {
-        self.has_right_lo = !self.has_right_lo;
         if self.has_right_lo {
             self.inner.push_unchecked(u4x2::from_hi(nib.to_u4hi()));
         } else {
             let i = self.inner.len() - 1;
             self.inner[i].set_lo(nib)
         }
+        self.has_right_lo = !self.has_right_lo;
     }
 
     /// Inserts a nibble into the vector at the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len` or if the vector is full.
     pub fn insert<T: u4>(&mut self, index: usize, nib: T) {
+        let len = self.len();
+        assert!(index <= len, "insertion index {} out of range for vector of length {}", index, len);
         if self.has_right_lo {
-            self.push(u4lo::from_lo(0));
+            self.inner.push(u4x2::from_byte(0));
         }
         shift_right(self.inner.as_mut_slice(), index);
         set_nib(self.inner.as_mut_slice(), index, nib);
+        self.has_right_lo = !self.has_right_lo;
     }
 
     /// Inserts a nibble into the vector at the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
     pub fn try_insert<T: u4>(&mut self, index: usize, nib: T) -> Result<(), CapacityError<T>> {
+        let len = self.len();
+        assert!(index <= len, "insertion index {} out of range for vector of length {}", index, len);
         let lo = nib.to_u4lo();
         if self.has_right_lo {
             self.inner.try_push(u4x2::from_byte(0)).map_err(|_| CapacityError::new(nib))?;
         }
         shift_right(self.inner.as_mut_slice(), index);
         set_nib(self.inner.as_mut_slice(), index, lo);
+        self.has_right_lo = !self.has_right_lo;
         Ok(())
     }
 
+    /// Resizes the vector to the given length, leaving any new nibbles zeroed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length exceeds the capacity.
+    fn set_len_zeroed(&mut self, len: usize) {
+        let old_len = self.len();
+        let pairs = (len + 1) >> 1;
+        assert!(pairs <= self.inner.capacity(), "length {} exceeds capacity of vector", len);
+        self.inner.truncate(pairs);
+        while self.inner.len() < pairs {
+            self.inner.push(u4x2::from_byte(0));
+        }
+        self.has_right_lo = len & 1 == 0;
+        if len & 1 == 1 {
+            let i = self.inner.len() - 1;
+            self.inner[i].set_lo(u4lo::from_lo(0));
+        }
+        if old_len & 1 == 1 && len > old_len {
+            set_nib(self.inner.as_mut_slice(), old_len, u4lo::from_lo(0));
+        }
+    }
+
+    /// Shortens the vector to the given length.
+    ///
+    /// Does nothing if the vector is already shorter than that.
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len() {
+            self.set_len_zeroed(len);
+        }
+    }
+
+    /// Resizes the vector to the given length, filling any new space with the given nibble.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length exceeds the capacity.
+    pub fn resize<T: u4>(&mut self, len: usize, nib: T) {
+        let old_len = self.len();
+        self.set_len_zeroed(len);
+        if len > old_len {
+            fill_nibs(self.inner.as_mut_slice(), old_len, len - old_len, nib.to_u4lo());
+        }
+    }
+
+    /// Copies all the nibbles from a slice onto the end of this vector.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the nibbles don't fit in the vector.
+    pub fn extend_from_nibs<S: ?Sized + NibSliceExt>(&mut self, nibs: &S) {
+        let len = self.len();
+        self.replace_nibs(len, len, nibs);
+    }
+
+    /// Keeps only the nibbles for which the given predicate returns `true`.
+    pub fn retain<F: FnMut(u4lo) -> bool>(&mut self, mut f: F) {
+        let len = self.len();
+        let mut write = 0;
+        for read in 0..len {
+            let nib: u4lo = get_nib(self.inner.as_slice(), read);
+            if f(nib) {
+                set_nib(self.inner.as_mut_slice(), write, nib);
+                write += 1;
+            }
+        }
+        self.truncate(write);
+    }
+
+    /// Removes consecutive repeated nibbles from the vector.
+    ///
+    /// If the vector is sorted, this removes all duplicates.
+    pub fn dedup(&mut self) {
+        let mut prev = None;
+        self.retain(|nib| {
+            let keep = prev.map_or(true, |prev: u4lo| prev != nib);
+            prev = Some(nib);
+            keep
+        });
+    }
+
+    /// Replaces the nibbles in `start..end` with the given ones, shifting the tail once.
+    fn replace_nibs<S: ?Sized + NibSliceExt>(&mut self, start: usize, end: usize, nibs: &S) {
+        let len = self.len();
+        let count = nibs.len();
+        let new_len = len - (end - start) + count;
+        if new_len > len {
+            self.set_len_zeroed(new_len);
+        }
+        move_nibs(self.inner.as_mut_slice(), end, start + count, len - end);
+        copy_nibs(self.inner.as_mut_slice(), start, nibs.nibble_pairs().as_slice(), !nibs.is_aligned() as usize, count);
+        if new_len < len {
+            self.set_len_zeroed(new_len);
+        }
+    }
+
+    /// Inserts all the nibbles from a slice at the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len` or if the nibbles don't fit in the vector.
+    pub fn insert_nibs<S: ?Sized + NibSliceExt>(&mut self, index: usize, nibs: &S) {
+        let len = self.len();
+        assert!(index <= len, "insertion index {} out of range for vector of length {}", index, len);
+        self.replace_nibs(index, index, nibs);
+    }
+
+    /// Removes the given range of nibbles from the vector.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn remove_range<R: RangeBounds<usize>>(&mut self, range: R) {
+        let len = self.len();
+        let (start, end) = nib_range(range, len);
+        move_nibs(self.inner.as_mut_slice(), end, start, len - end);
+        self.set_len_zeroed(len - (end - start));
+    }
+
+    /// Removes the given range of nibbles from the vector, returning them as an iterator.
+    ///
+    /// The nibbles are removed when the iterator is dropped, even if it isn't fully consumed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn drain<R: RangeBounds<usize>>(&mut self, range: R) -> Drain<A> {
+        let (start, end) = nib_range(range, self.len());
+        Drain { vec: self, start, end, front: start, back: end }
+    }
+
+    /// Replaces the given range of nibbles with the ones from the iterator, returning the removed
+    /// nibbles.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds or if the result doesn't fit in the vector.
+    pub fn splice<R, I, T>(&mut self, range: R, replace_with: I) -> NibArrayVec<A>
+    where
+        R: RangeBounds<usize>,
+        I: IntoIterator<Item = T>,
+        T: u4,
+    {
+        let (start, end) = nib_range(range, self.len());
+        let mut removed = NibArrayVec::new();
+        removed.replace_nibs(0, 0, &self.slice(start..end));
+        let replacement: NibArrayVec<A> = replace_with.into_iter().collect();
+        self.replace_nibs(start, end, &replacement);
+        removed
+    }
+
     fn discard_at(&mut self, index: usize) {
         shift_left(self.inner.as_mut_slice(), index);
         self.has_right_lo = !self.has_right_lo;
@@ -109,7 +300,13 @@ impl<A: Array<Item = u4x2>> NibArrayVec<A> {
     }
 
     /// Removes a nibble from the vector at the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= len`.
     pub fn remove<T: u4>(&mut self, index: usize) -> T {
+        let len = self.len();
+        assert!(index < len, "removal index {} out of range for vector of length {}", index, len);
         let ret = get_nib(self.inner.as_slice(), index);
         self.discard_at(index);
         ret
@@ -126,11 +323,16 @@ impl<A: Array<Item = u4x2>> NibArrayVec<A> {
 
     /// Removes a nibble from the vector, converting it to a high-order nibble.
     pub fn pop<T: u4>(&mut self) -> Option<T> {
-        self.has_right_lo = !self.has_right_lo;
         if self.has_right_lo {
-            Some(T::from_lo(self.inner[self.inner.len() - 1].lo().to_lo()))
+            let i = self.inner.len().checked_sub(1)?;
+            let ret = T::from_lo(self.inner[i].lo().to_lo());
+            self.inner[i].set_lo(u4lo::from_lo(0));
+            self.has_right_lo = false;
+            Some(ret)
         } else {
-            self.inner.pop().map(|pair| T::from_hi(pair.hi().to_hi()))
+            let pair = self.inner.pop()?;
+            self.has_right_lo = true;
+            Some(T::from_hi(pair.hi().to_hi()))
         }
     }
 
@@ -186,6 +388,14 @@ impl<A: Array<Item = u4x2>> Default for NibArrayVec<A> {
         NibArrayVec::new()
     }
 }
+impl<'a, A: Array<Item = u4x2>> TryFrom<NibSlice<'a>> for NibArrayVec<A> {
+    type Error = TryFromNibSliceError;
+    fn try_from(slice: NibSlice<'a>) -> Result<Self, TryFromNibSliceError> {
+        let mut vec = Self::new();
+        vec.try_extend_from_nibs(&slice).map_err(|_| TryFromNibSliceError(()))?;
+        Ok(vec)
+    }
+}
 impl<A: Array<Item = u4x2>, T: u4> FromIterator<T> for NibArrayVec<A> {
     fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
         let mut vec = Self::new();
@@ -229,6 +439,49 @@ impl<A: Array<Item = u4x2>> Extend<u4x2> for NibArrayVec<A> {
         }
     }
 }
+/// A draining iterator for `NibArrayVec`.
+///
+/// Created by [`NibArrayVec::drain`](struct.NibArrayVec.html#method.drain).
+#[derive(Debug)]
+pub struct Drain<'a, A: 'a + Array<Item = u4x2>> {
+    vec: &'a mut NibArrayVec<A>,
+    start: usize,
+    end: usize,
+    front: usize,
+    back: usize,
+}
+impl<'a, A: Array<Item = u4x2>> Iterator for Drain<'a, A> {
+    type Item = u4lo;
+    fn next(&mut self) -> Option<u4lo> {
+        if self.front < self.back {
+            self.front += 1;
+            Some(get_nib(self.vec.inner.as_slice(), self.front - 1))
+        } else {
+            None
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.back - self.front;
+        (len, Some(len))
+    }
+}
+impl<'a, A: Array<Item = u4x2>> DoubleEndedIterator for Drain<'a, A> {
+    fn next_back(&mut self) -> Option<u4lo> {
+        if self.front < self.back {
+            self.back -= 1;
+            Some(get_nib(self.vec.inner.as_slice(), self.back))
+        } else {
+            None
+        }
+    }
+}
+impl<'a, A: Array<Item = u4x2>> ExactSizeIterator for Drain<'a, A> {}
+impl<'a, A: Array<Item = u4x2>> Drop for Drain<'a, A> {
+    fn drop(&mut self) {
+        self.vec.remove_range(self.start..self.end);
+    }
+}
+
 impl<A: Array<Item = u4x2>> slice::private::Sealed for NibArrayVec<A> {
     #[inline(always)]
     fn has_left_hi(&self) -> bool { true }
@@ -244,6 +497,17 @@ impl<A: Array<Item = u4x2>> slice::private::SealedMut for NibArrayVec<A> {
 impl<A: Array<Item = u4x2>> slice::NibSliceExt for NibArrayVec<A> {}
 impl<A: Array<Item = u4x2>> slice::NibSliceMutExt for NibArrayVec<A> {}
 
+/// Copies exactly `A::capacity()` pairs' worth of nibbles out of a slice.
+fn pairs_from_nibs<A: Array<Item = u4x2>>(slice: NibSlice, len: usize) -> A {
+    let mut inner = ArrayVec::<A>::new();
+    while !inner.is_full() {
+        inner.push(u4x2::from_byte(0));
+    }
+    let start = !slice.is_aligned() as usize;
+    copy_nibs(inner.as_mut_slice(), 0, slice.nibble_pairs().as_slice(), start, len);
+    inner.into_inner().unwrap_or_else(|_| unreachable!())
+}
+
 /// An array with an even number of nibbles.
 pub struct NibArrayEven<A: Array<Item = u4x2>> {
     inner: A,
@@ -253,6 +517,17 @@ impl<A: Array<Item = u4x2>> From<A> for NibArrayEven<A> {
         NibArrayEven { inner }
     }
 }
+impl<'a, A: Array<Item = u4x2>> TryFrom<NibSlice<'a>> for NibArrayEven<A> {
+    type Error = TryFromNibSliceError;
+    fn try_from(slice: NibSlice<'a>) -> Result<Self, TryFromNibSliceError> {
+        let len = A::capacity() << 1;
+        if slice.len() == len {
+            Ok(NibArrayEven { inner: pairs_from_nibs(slice, len) })
+        } else {
+            Err(TryFromNibSliceError(()))
+        }
+    }
+}
 impl<A: Array<Item = u4x2>> ops::Deref for NibArrayEven<A> {
     type Target = NibSliceFull;
     fn deref(&self) -> &NibSliceFull {
@@ -294,6 +569,17 @@ impl<A: Array<Item = u4x2>> From<A> for NibArrayOdd<A> {
         NibArrayOdd { inner }
     }
 }
+impl<'a, A: Array<Item = u4x2>> TryFrom<NibSlice<'a>> for NibArrayOdd<A> {
+    type Error = TryFromNibSliceError;
+    fn try_from(slice: NibSlice<'a>) -> Result<Self, TryFromNibSliceError> {
+        let len = (A::capacity() << 1).wrapping_sub(1);
+        if slice.len() == len {
+            Ok(NibArrayOdd { inner: pairs_from_nibs(slice, len) })
+        } else {
+            Err(TryFromNibSliceError(()))
+        }
+    }
+}
 impl<A: Array<Item = u4x2>> ops::Deref for NibArrayOdd<A> {
     type Target = NibSliceNoR;
     fn deref(&self) -> &NibSliceNoR {
@@ -348,6 +634,19 @@ impl<A: Array<Item = u4x2>> NibArray<A> {
         }
     }
 }
+impl<'a, A: Array<Item = u4x2>> TryFrom<NibSlice<'a>> for NibArray<A> {
+    type Error = TryFromNibSliceError;
+    fn try_from(slice: NibSlice<'a>) -> Result<Self, TryFromNibSliceError> {
+        NibArrayEven::try_from(slice).map(NibArray::Even)
+            .or_else(|_| NibArrayOdd::try_from(slice).map(NibArray::Odd))
+    }
+}
+impl<'a, 'b, A: Array<Item = u4x2>> TryFrom<&'b NibSlice<'a>> for NibArray<A> {
+    type Error = TryFromNibSliceError;
+    fn try_from(slice: &'b NibSlice<'a>) -> Result<Self, TryFromNibSliceError> {
+        NibArray::try_from(*slice)
+    }
+}
 impl<A: Array<Item = u4x2>> slice::private::Sealed for NibArray<A> {
     #[inline(always)]
     fn has_left_hi(&self) -> bool { true }
@@ -376,3 +675,424 @@ impl<A: Array<Item = u4x2>> slice::private::SealedMut for NibArray<A> {
 }
 impl<A: Array<Item = u4x2>> slice::NibSliceExt for NibArray<A> {}
 impl<A: Array<Item = u4x2>> slice::NibSliceMutExt for NibArray<A> {}
+
+/// Error returned when a slice doesn't have the right length to convert into an array.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct TryFromNibSliceError(());
+impl TryFromNibSliceError {
+    /// User-friendly description of the error.
+    pub fn description(&self) -> &'static str {
+        "slice had the wrong length for the array"
+    }
+}
+impl fmt::Display for TryFromNibSliceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(self.description())
+    }
+}
+#[cfg(feature = "std")]
+impl ::std::error::Error for TryFromNibSliceError {
+    fn description(&self) -> &str {
+        self.description()
+    }
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// Marker for the number of nibbles in a `NibArr`.
+#[derive(Clone, Copy, Debug)]
+pub struct NibLen<const N: usize>;
+
+/// Storage for a `NibArr` with a given number of nibbles.
+///
+/// This is implemented for `NibLen<N>` for every `N` up to 128, which is enough for the hex
+/// digest of a 512-bit hash.
+pub trait NibArrLen: private::Sealed {
+    /// Pairs which hold the nibbles.
+    type Pairs: Copy + AsRef<[u4x2]> + AsMut<[u4x2]>;
+
+    /// Bytes with the same layout as the pairs.
+    type Bytes: Copy;
+
+    /// Slice type the array dereferences to.
+    type Slice: ?Sized + NibSliceMutExt;
+
+    /// Pairs with every nibble zero.
+    const ZERO: Self::Pairs;
+
+    #[doc(hidden)]
+    fn slice(pairs: &[u4x2]) -> &Self::Slice;
+
+    #[doc(hidden)]
+    fn slice_mut(pairs: &mut [u4x2]) -> &mut Self::Slice;
+}
+
+macro_rules! do_nib_len {
+    ($slice:ident: $($n:tt)*) => {
+        $(
+            impl private::Sealed for NibLen<$n> {}
+            impl NibArrLen for NibLen<$n> {
+                type Pairs = [u4x2; ($n + 1) / 2];
+                type Bytes = [u8; ($n + 1) / 2];
+                type Slice = $slice;
+                const ZERO: Self::Pairs = [u4x2::from_byte(0); ($n + 1) / 2];
+                fn slice(pairs: &[u4x2]) -> &$slice {
+                    $slice::from_slice(pairs)
+                }
+                fn slice_mut(pairs: &mut [u4x2]) -> &mut $slice {
+                    $slice::from_mut_slice(pairs)
+                }
+            }
+        )*
+    }
+}
+
+do_nib_len! {
+    NibSliceFull:
+    0 2 4 6 8 10 12 14 16 18 20 22 24 26 28 30
+    32 34 36 38 40 42 44 46 48 50 52 54 56 58 60 62
+    64 66 68 70 72 74 76 78 80 82 84 86 88 90 92 94
+    96 98 100 102 104 106 108 110 112 114 116 118 120 122 124 126
+    128
+}
+do_nib_len! {
+    NibSliceNoR:
+    1 3 5 7 9 11 13 15 17 19 21 23 25 27 29 31
+    33 35 37 39 41 43 45 47 49 51 53 55 57 59 61 63
+    65 67 69 71 73 75 77 79 81 83 85 87 89 91 93 95
+    97 99 101 103 105 107 109 111 113 115 117 119 121 123 125 127
+}
+
+/// Reinterprets one `Copy` type as another of the same size.
+union Cast<A: Copy, B: Copy> {
+    from: A,
+    to: B,
+}
+
+/// An array of exactly `N` nibbles, stored in `(N + 1) / 2` bytes.
+///
+/// Even arrays dereference to `NibSliceFull`, and odd arrays to `NibSliceNoR`. Only lengths up to
+/// 128 are supported; see `NibArrLen`.
+#[derive(Clone, Copy)]
+pub struct NibArr<const N: usize> where NibLen<N>: NibArrLen {
+    inner: <NibLen<N> as NibArrLen>::Pairs,
+}
+impl<const N: usize> NibArr<N> where NibLen<N>: NibArrLen {
+    /// Creates an array where every nibble is zero.
+    pub const fn new() -> Self {
+        NibArr { inner: <NibLen<N> as NibArrLen>::ZERO }
+    }
+
+    /// Creates an array from its pairs.
+    ///
+    /// For odd lengths, the low-order nibble of the last pair is ignored.
+    pub const fn from_pairs(inner: <NibLen<N> as NibArrLen>::Pairs) -> Self {
+        NibArr { inner }
+    }
+
+    /// Creates an array from bytes.
+    ///
+    /// For odd lengths, the low-order bits of the last byte are ignored.
+    pub const fn from_bytes(bytes: <NibLen<N> as NibArrLen>::Bytes) -> Self {
+        // both types are arrays of `(N + 1) / 2` bytes
+        NibArr { inner: unsafe { Cast { from: bytes }.to } }
+    }
+
+    /// Number of nibbles in the array.
+    pub const fn len(&self) -> usize {
+        N
+    }
+
+    /// Whether the array is empty.
+    pub const fn is_empty(&self) -> bool {
+        N == 0
+    }
+
+    /// The pairs which hold the nibbles.
+    pub fn as_pairs(&self) -> &[u4x2] {
+        self.inner.as_ref()
+    }
+}
+impl<const N: usize> Default for NibArr<N> where NibLen<N>: NibArrLen {
+    fn default() -> Self {
+        NibArr::new()
+    }
+}
+impl<'a, const N: usize> TryFrom<NibSlice<'a>> for NibArr<N> where NibLen<N>: NibArrLen {
+    type Error = TryFromNibSliceError;
+    fn try_from(slice: NibSlice<'a>) -> Result<Self, TryFromNibSliceError> {
+        if slice.len() != N {
+            return Err(TryFromNibSliceError(()));
+        }
+        let mut arr = NibArr::new();
+        let start = !slice.is_aligned() as usize;
+        copy_nibs(arr.inner.as_mut(), 0, slice.nibble_pairs().as_slice(), start, N);
+        Ok(arr)
+    }
+}
+impl<const N: usize> ops::Deref for NibArr<N> where NibLen<N>: NibArrLen {
+    type Target = <NibLen<N> as NibArrLen>::Slice;
+    fn deref(&self) -> &Self::Target {
+        <NibLen<N> as NibArrLen>::slice(self.inner.as_ref())
+    }
+}
+impl<const N: usize> ops::DerefMut for NibArr<N> where NibLen<N>: NibArrLen {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        <NibLen<N> as NibArrLen>::slice_mut(self.inner.as_mut())
+    }
+}
+impl<const N: usize> slice::private::Sealed for NibArr<N> where NibLen<N>: NibArrLen {
+    #[inline(always)]
+    fn has_left_hi(&self) -> bool { true }
+    #[inline(always)]
+    fn has_right_lo(&self) -> bool { N & 1 == 0 }
+    #[inline(always)]
+    fn iter(&self) -> stdslice::Iter<u4x2> { self.inner.as_ref().iter() }
+}
+impl<const N: usize> slice::private::SealedMut for NibArr<N> where NibLen<N>: NibArrLen {
+    #[inline(always)]
+    fn iter_mut(&mut self) -> stdslice::IterMut<u4x2> { self.inner.as_mut().iter_mut() }
+}
+impl<const N: usize> slice::NibSliceExt for NibArr<N> where NibLen<N>: NibArrLen {}
+impl<const N: usize> slice::NibSliceMutExt for NibArr<N> where NibLen<N>: NibArrLen {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::XorShift;
+
+    fn nibs<A: Array<Item = u4x2>>(vec: &NibArrayVec<A>) -> Vec<u8> {
+        vec.nibbles().map(|nib| nib.to_lo()).collect()
+    }
+
+    fn nibs_of<S: ?Sized + NibSliceExt>(slice: &S) -> Vec<u8> {
+        slice.nibbles().map(|nib| nib.to_lo()).collect()
+    }
+
+    fn nib_vec(nibs: &[u8]) -> NibArrayVec<[u4x2; 8]> {
+        nibs.iter().map(|&nib| u4lo::from_lo(nib)).collect()
+    }
+
+    fn check(vec: &NibArrayVec<[u4x2; 8]>, model: &[u8]) {
+        assert_eq!(nibs(vec), model);
+        assert_eq!(vec.len(), model.len());
+        assert_eq!(vec.is_empty(), model.is_empty());
+        assert_eq!(vec.is_full(), model.len() == 16);
+        assert_eq!(vec.capacity(), 16);
+        if model.len() & 1 == 1 {
+            assert_eq!(vec.inner[model.len() >> 1].lo().to_lo(), 0, "padding must stay zero");
+        }
+    }
+
+    #[test]
+    fn model() {
+        let mut rng = XorShift(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..20 {
+            let mut vec = NibArrayVec::<[u4x2; 8]>::new();
+            let mut model = Vec::new();
+            for _ in 0..200 {
+                let len = model.len();
+                match rng.below(8) {
+                    0 | 1 => {
+                        let nib = rng.nib();
+                        assert_eq!(vec.try_push(u4lo::from_lo(nib)).is_ok(), len < 16);
+                        if len < 16 {
+                            model.push(nib);
+                        }
+                    }
+                    2 => assert_eq!(vec.pop::<u4lo>().map(|nib| nib.to_lo()), model.pop()),
+                    3 => {
+                        let (idx, nib) = (rng.below(len + 1), rng.nib());
+                        assert_eq!(vec.try_insert(idx, u4lo::from_lo(nib)).is_ok(), len < 16);
+                        if len < 16 {
+                            model.insert(idx, nib);
+                        }
+                    }
+                    4 => if len > 0 {
+                        let idx = rng.below(len);
+                        assert_eq!(vec.remove::<u4lo>(idx).to_lo(), model.remove(idx));
+                    },
+                    5 => {
+                        let (start, end) = rng.range(len);
+                        let drained: Vec<u8> = vec.drain(start..end).map(|nib| nib.to_lo()).collect();
+                        assert_eq!(drained, model.drain(start..end).collect::<Vec<_>>());
+                    }
+                    6 => {
+                        let (start, end) = rng.range(len);
+                        let count = rng.below(16 - len + end - start + 1);
+                        let new: Vec<u8> = (0..count).map(|_| rng.nib()).collect();
+                        let removed = vec.splice(start..end, new.iter().map(|&nib| u4lo::from_lo(nib)));
+                        assert_eq!(nibs(&removed), model.splice(start..end, new).collect::<Vec<_>>());
+                    }
+                    _ => {
+                        let other: Vec<u8> = (0..rng.below(16 - len + 1)).map(|_| rng.nib()).collect();
+                        let idx = rng.below(len + 1);
+                        vec.insert_nibs(idx, &nib_vec(&other));
+                        for (i, &nib) in other.iter().enumerate() {
+                            model.insert(idx + i, nib);
+                        }
+                    }
+                }
+                check(&vec, &model);
+            }
+        }
+    }
+
+    #[test]
+    fn into_array() {
+        let mut vec = nib_vec(&[0; 15]);
+        assert!(vec.pop::<u4lo>().is_some());
+        let vec = vec.into_odd_array().unwrap_err();
+        let mut vec = vec.into_even_array().unwrap_err();
+        vec.push(u4lo::from_lo(1));
+        let vec = vec.into_even_array().unwrap_err();
+        let odd = vec.into_odd_array().unwrap();
+        assert_eq!(odd.len(), 15);
+        let mut vec = nib_vec(&[0; 15]);
+        vec.push(u4lo::from_lo(1));
+        let even = vec.into_even_array().unwrap();
+        assert_eq!(even.len(), 16);
+        assert_eq!(even.last().map(|nib| nib.to_lo()), Some(1));
+    }
+
+    #[test]
+    fn push() {
+        let mut vec = NibArrayVec::<[u4x2; 2]>::new();
+        vec.push(u4lo::from_lo(1));
+        vec.push(u4lo::from_lo(2));
+        assert!(vec.try_push(u4lo::from_lo(3)).is_ok());
+        assert!(vec.try_push(u4lo::from_lo(4)).is_ok());
+        assert!(vec.try_push(u4lo::from_lo(5)).is_err());
+        assert_eq!(nibs(&vec), [1, 2, 3, 4]);
+        assert_eq!(vec.pop::<u4lo>().map(|nib| nib.to_lo()), Some(4));
+        assert_eq!(vec.pop::<u4lo>().map(|nib| nib.to_lo()), Some(3));
+        unsafe {
+            vec.push_unchecked(u4lo::from_lo(6));
+            vec.push_unchecked(u4lo::from_lo(7));
+        }
+        assert_eq!(nibs(&vec), [1, 2, 6, 7]);
+        assert!(vec.is_full());
+    }
+
+    #[test]
+    fn empty() {
+        let mut vec = NibArrayVec::<[u4x2; 0]>::new();
+        assert!(vec.is_empty() && vec.is_full());
+        assert_eq!(vec.capacity(), 0);
+        assert_eq!(vec.pop::<u4lo>(), None);
+        assert!(vec.try_push(u4lo::from_lo(1)).is_err());
+    }
+
+    #[test]
+    fn nib_arr() {
+        use core::mem::size_of;
+        assert_eq!(size_of::<NibArr<5>>(), 3);
+        assert_eq!(size_of::<NibArr<6>>(), 3);
+
+        const ARR: NibArr<5> = NibArr::from_bytes([0x12, 0x34, 0x5F]);
+        let arr: &NibSliceNoR = &ARR;
+        assert_eq!(nibs_of(arr), [1, 2, 3, 4, 5]);
+        assert_eq!(ARR, "12345".parse::<NibArr<5>>().unwrap());
+        assert_eq!(NibArr::<4>::default(), NibArr::<4>::from_bytes([0, 0]));
+
+        let vec = nib_vec(&[0, 1, 2, 3, 4, 5]);
+        let arr = NibArr::<4>::try_from(vec.slice(1..5)).unwrap();
+        let arr: &NibSliceFull = &arr;
+        assert_eq!(nibs_of(arr), [1, 2, 3, 4]);
+        assert!(NibArr::<4>::try_from(vec.slice(1..4)).is_err());
+        let arr = NibArr::<3>::try_from(vec.slice(1..4)).unwrap();
+        assert_eq!(nibs_of(&arr), [1, 2, 3]);
+        assert!(NibArr::<3>::try_from(vec.slice(1..5)).is_err());
+    }
+
+    #[test]
+    fn nib_arr_new() {
+        const ODD: NibArr<3> = NibArr::new();
+        const EVEN: NibArr<4> = NibArr::new();
+        assert_eq!(nibs_of(&ODD), [0, 0, 0]);
+        assert_eq!(ODD, NibArr::<3>::default());
+        assert_eq!(EVEN, NibArr::<4>::default());
+        assert_eq!(ODD.len(), 3);
+        assert_eq!(nibs_of(&NibArr::<1>::default()), [0]);
+        assert!(NibArr::<0>::new().is_empty());
+
+        // padding is ignored, so it never shows up in comparisons
+        assert_eq!(NibArr::<3>::from_bytes([0x12, 0x3F]), NibArr::<3>::from_bytes([0x12, 0x30]));
+    }
+
+    #[test]
+    fn nib_arr_parse() {
+        use base::ParseNibbleError;
+        assert_eq!("123".parse::<NibArr<3>>().map(|arr| nibs_of(&arr)), Ok(vec![1, 2, 3]));
+        assert_eq!("12".parse::<NibArr<3>>(), Err(ParseNibbleError::BadFormat));
+        assert_eq!("1234".parse::<NibArr<3>>(), Err(ParseNibbleError::TooLarge));
+        assert_eq!("1234".parse::<NibArr<5>>(), Err(ParseNibbleError::BadFormat));
+        assert_eq!("123456".parse::<NibArr<5>>(), Err(ParseNibbleError::TooLarge));
+        assert_eq!("".parse::<NibArr<1>>(), Err(ParseNibbleError::Empty));
+        assert_eq!("".parse::<NibArr<0>>(), Ok(NibArr::new()));
+        assert_eq!("12g".parse::<NibArr<3>>(), Err(ParseNibbleError::BadFormat));
+
+        // the hex digest of a 512-bit hash
+        let digest: String = (0..128).map(|i| b"0123456789abcdef"[i % 16] as char).collect();
+        let arr: NibArr<128> = digest.parse().unwrap();
+        assert_eq!(core::mem::size_of::<NibArr<128>>(), 64);
+        assert_eq!(arr.to_aligned_bytes()[..2], [0x01, 0x23]);
+        assert_eq!(digest[1..].parse::<NibArr<127>>().map(|arr| arr.len()), Ok(127));
+        assert_eq!(digest[1..].parse::<NibArr<128>>(), Err(ParseNibbleError::BadFormat));
+    }
+
+    #[test]
+    fn conversions() {
+        let vec = nib_vec(&[1, 2, 3, 4, 5]);
+        let mut small = NibArrayVec::<[u4x2; 4]>::from_nibs(&vec.slice(1..));
+        assert_eq!(nibs(&small), [2, 3, 4, 5]);
+        assert!(small.try_extend_from_nibs(&vec.slice(..3)).is_ok());
+        assert_eq!(nibs(&small), [2, 3, 4, 5, 1, 2, 3]);
+        assert!(small.try_extend_from_nibs(&vec.slice(..2)).is_err());
+        assert_eq!(nibs(&small), [2, 3, 4, 5, 1, 2, 3]);
+
+        assert!(NibArrayVec::<[u4x2; 2]>::try_from(small.slice(..5)).is_err());
+        let heap = ::vec::NibVec::from(small);
+        assert_eq!(nibs_of(&heap), [2, 3, 4, 5, 1, 2, 3]);
+
+        let array = NibArray::<[u4x2; 2]>::try_from(&heap.slice(1..5)).unwrap();
+        assert_eq!(nibs_of(&array), [3, 4, 5, 1]);
+        let array = NibArray::<[u4x2; 2]>::try_from(heap.slice(1..4)).unwrap();
+        assert_eq!(nibs_of(&array), [3, 4, 5]);
+        assert!(NibArray::<[u4x2; 2]>::try_from(heap.slice(1..3)).is_err());
+        assert_eq!(nibs_of(&array.to_vec()), [3, 4, 5]);
+
+        let array: NibArray<[u4x2; 2]> = "abc".parse().unwrap();
+        assert_eq!(nibs_of(&array), [0xa, 0xb, 0xc]);
+        assert!("abcde".parse::<NibArray<[u4x2; 2]>>().is_err());
+        assert!("ab".parse::<NibArray<[u4x2; 2]>>().is_err());
+    }
+
+    #[test]
+    fn drain_splice() {
+        let mut vec = nib_vec(&[1, 2, 3, 4, 5, 6, 7]);
+        let drained: Vec<u8> = vec.drain(1..4).map(|nib| nib.to_lo()).collect();
+        assert_eq!((drained, nibs(&vec)), (vec![2, 3, 4], vec![1, 5, 6, 7]));
+
+        let removed = vec.splice(1..2, (8..13).map(u4lo::from_lo));
+        assert_eq!((nibs(&removed), nibs(&vec)), (vec![5], vec![1, 8, 9, 10, 11, 12, 6, 7]));
+    }
+
+    #[test]
+    fn insert_remove_range() {
+        let mut vec = nib_vec(&[1, 2, 3]);
+        vec.insert_nibs(1, &nib_vec(&[4, 5, 6, 7]));
+        assert_eq!(nibs(&vec), [1, 4, 5, 6, 7, 2, 3]);
+        vec.remove_range(2..5);
+        assert_eq!(nibs(&vec), [1, 4, 2, 3]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn insert_overflow() {
+        let mut vec = nib_vec(&[0; 15]);
+        vec.insert_nibs(3, &nib_vec(&[1, 2]));
+    }
+}
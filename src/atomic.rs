@@ -0,0 +1,362 @@
+//! Atomic access to nibbles which share their byte with another nibble.
+//!
+//! Every write is a compare-and-swap loop over the whole byte, so concurrent writes to the other
+//! nibble of the same byte are never lost.
+use core::marker::PhantomData;
+use core::ops::RangeBounds;
+use core::sync::atomic::{AtomicU8, Ordering};
+use base::{u4, u4lo};
+use order::{HiFirst, NibOrder};
+use common::{is_hi, nib_range};
+
+/// Ordering to use for the load which starts a compare-and-swap loop.
+fn fetch_order(order: Ordering) -> Ordering {
+    match order {
+        Ordering::Release => Ordering::Relaxed,
+        Ordering::AcqRel => Ordering::Acquire,
+        order => order,
+    }
+}
+
+/// Loads the nibble at the given shift in a byte.
+#[inline]
+fn load_nib(byte: &AtomicU8, shift: u32, order: Ordering) -> u8 {
+    (byte.load(order) >> shift) & 0xF
+}
+
+/// Replaces the nibble at the given shift in a byte with the result of `f`, until that succeeds
+/// without the byte changing under it.
+///
+/// Returns the old nibble, or `Err` with the current one if `f` returns `None`.
+fn update_nib<F>(byte: &AtomicU8, shift: u32, set_order: Ordering, fetch_order: Ordering, mut f: F)
+    -> Result<u8, u8>
+    where F: FnMut(u8) -> Option<u8>
+{
+    let mut prev = byte.load(fetch_order);
+    loop {
+        let old = (prev >> shift) & 0xF;
+        let new = match f(old) {
+            Some(new) => (prev & !(0xF << shift)) | ((new & 0xF) << shift),
+            None => return Err(old),
+        };
+        match byte.compare_exchange_weak(prev, new, set_order, fetch_order) {
+            Ok(_) => return Ok(old),
+            Err(next) => prev = next,
+        }
+    }
+}
+
+macro_rules! do_atomic {
+    ($($(#[$attr:meta])* $t:ident ($shift:expr);)*) => {
+        $(
+            $(#[$attr])*
+            #[repr(transparent)]
+            pub struct $t {
+                inner: AtomicU8,
+            }
+            impl $t {
+                /// Views the nibble in an atomic byte.
+                pub fn from_atomic(inner: &AtomicU8) -> &$t {
+                    unsafe { &*(inner as *const AtomicU8 as *const $t) }
+                }
+
+                /// Gets the whole atomic byte containing this nibble.
+                pub fn as_atomic(&self) -> &AtomicU8 {
+                    &self.inner
+                }
+
+                /// Loads the nibble.
+                pub fn load<T: u4>(&self, order: Ordering) -> T {
+                    T::from_lo(load_nib(&self.inner, $shift, order))
+                }
+
+                /// Stores a nibble, leaving the other nibble in the byte untouched.
+                pub fn store<T: u4>(&self, nib: T, order: Ordering) {
+                    self.swap(nib, order);
+                }
+
+                /// Stores a nibble, returning the old one.
+                pub fn swap<T: u4>(&self, nib: T, order: Ordering) -> T {
+                    let nib = nib.to_lo();
+                    update_nib(&self.inner, $shift, order, fetch_order(order), |_| Some(nib))
+                        .map(T::from_lo)
+                        .unwrap_or_else(|_| unreachable!())
+                }
+
+                /// Stores `new` if the nibble is `current`, returning the old nibble.
+                ///
+                /// Unlike a plain compare-exchange on the byte, this only fails if the nibble
+                /// itself differs, and not if the other nibble in the byte changed.
+                pub fn compare_exchange<T: u4>(
+                    &self, current: T, new: T, success: Ordering, failure: Ordering
+                ) -> Result<T, T> {
+                    let (current, new) = (current.to_lo(), new.to_lo());
+                    self.fetch_update(success, failure, |old: T| {
+                        if old.to_lo() == current { Some(T::from_lo(new)) } else { None }
+                    })
+                }
+
+                /// Stores the result of `f` on the nibble, retrying until it wasn't changed in the
+                /// meantime, and returning the old nibble.
+                ///
+                /// Stops and returns `Err` with the current nibble if `f` returns `None`.
+                pub fn fetch_update<T: u4, F>(
+                    &self, set_order: Ordering, fetch_order: Ordering, mut f: F
+                ) -> Result<T, T> where F: FnMut(T) -> Option<T> {
+                    let f = |old| f(T::from_lo(old)).map(|nib| nib.to_lo());
+                    update_nib(&self.inner, $shift, set_order, fetch_order, f)
+                        .map(T::from_lo)
+                        .map_err(T::from_lo)
+                }
+            }
+        )*
+    }
+}
+
+do_atomic! {
+    /// Atomic access to the high-order nibble of an `AtomicU8`.
+    AtomicU4Hi (4);
+    /// Atomic access to the low-order nibble of an `AtomicU8`.
+    AtomicU4Lo (0);
+}
+
+/// A slice of nibbles stored in atomic bytes.
+///
+/// Like `NibSliceCell`, this may start or end in the middle of a byte, and reads the bytes
+/// high-order nibble first unless created with `from_ordered_atomics`.
+pub struct AtomicNibSlice<'a, O: NibOrder = HiFirst> {
+    inner: &'a [AtomicU8],
+    start: usize,
+    len: usize,
+    nib_order: PhantomData<O>,
+}
+impl<'a, O: NibOrder> Clone for AtomicNibSlice<'a, O> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<'a, O: NibOrder> Copy for AtomicNibSlice<'a, O> {}
+impl<'a> AtomicNibSlice<'a> {
+    /// Views a slice of atomic bytes as nibbles.
+    pub fn from_atomics(inner: &'a [AtomicU8]) -> Self {
+        AtomicNibSlice::from_ordered_atomics(inner)
+    }
+}
+impl<'a, O: NibOrder> AtomicNibSlice<'a, O> {
+    /// Views a slice of atomic bytes, packed in this slice's order, as nibbles.
+    pub fn from_ordered_atomics(inner: &'a [AtomicU8]) -> Self {
+        AtomicNibSlice { inner, start: 0, len: inner.len() << 1, nib_order: PhantomData }
+    }
+
+    /// Gets the length of the slice.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Checks if the slice is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Gets the byte holding the nibble at the given index, and the nibble's shift in that byte.
+    fn locate(&self, idx: usize) -> (&'a AtomicU8, u32) {
+        assert!(idx < self.len, "index {} out of range for slice of length {}", idx, self.len);
+        let idx = self.start + idx;
+        (&self.inner[idx >> 1], if is_hi::<O>(idx) { 4 } else { 0 })
+    }
+
+    /// Loads the nibble at the given index, if it's in bounds.
+    pub fn get<T: u4>(&self, idx: usize, order: Ordering) -> Option<T> {
+        if idx < self.len {
+            Some(self.load(idx, order))
+        } else {
+            None
+        }
+    }
+
+    /// Loads the nibble at the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn load<T: u4>(&self, idx: usize, order: Ordering) -> T {
+        let (byte, shift) = self.locate(idx);
+        T::from_lo(load_nib(byte, shift, order))
+    }
+
+    /// Stores a nibble at the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn store<T: u4>(&self, idx: usize, nib: T, order: Ordering) {
+        self.swap(idx, nib, order);
+    }
+
+    /// Stores a nibble at the given index, returning the old one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn swap<T: u4>(&self, idx: usize, nib: T, order: Ordering) -> T {
+        let nib = nib.to_lo();
+        self.fetch_update(idx, order, fetch_order(order), |_| Some(T::from_lo(nib)))
+            .unwrap_or_else(|_| unreachable!())
+    }
+
+    /// Stores `new` at the given index if the nibble there is `current`, returning the old nibble.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn compare_exchange<T: u4>(
+        &self, idx: usize, current: T, new: T, success: Ordering, failure: Ordering
+    ) -> Result<T, T> {
+        let (current, new) = (current.to_lo(), new.to_lo());
+        self.fetch_update(idx, success, failure, |old: T| {
+            if old.to_lo() == current { Some(T::from_lo(new)) } else { None }
+        })
+    }
+
+    /// Stores the result of `f` on the nibble at the given index, retrying until it wasn't changed
+    /// in the meantime, and returning the old nibble.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn fetch_update<T: u4, F>(
+        &self, idx: usize, set_order: Ordering, fetch_order: Ordering, mut f: F
+    ) -> Result<T, T> where F: FnMut(T) -> Option<T> {
+        let (byte, shift) = self.locate(idx);
+        let f = |old| f(T::from_lo(old)).map(|nib| nib.to_lo());
+        update_nib(byte, shift, set_order, fetch_order, f)
+            .map(T::from_lo)
+            .map_err(T::from_lo)
+    }
+
+    /// Iterator which loads each nibble in the slice in turn.
+    pub fn nibbles(&self, order: Ordering) -> AtomicNibbles<'a, O> {
+        AtomicNibbles { slice: *self, order, front: 0, back: self.len }
+    }
+
+    /// Gets the nibbles in the given range as a subslice.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds or its start is greater than its end.
+    pub fn slice<R: RangeBounds<usize>>(&self, range: R) -> AtomicNibSlice<'a, O> {
+        let (start, end) = nib_range(range, self.len);
+        AtomicNibSlice { start: self.start + start, len: end - start, ..*self }
+    }
+
+    /// Divides the slice into two at the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mid > len`.
+    pub fn split_at(&self, mid: usize) -> (AtomicNibSlice<'a, O>, AtomicNibSlice<'a, O>) {
+        let len = self.len;
+        assert!(mid <= len, "split index {} out of range for slice of length {}", mid, len);
+        (self.slice(..mid), self.slice(mid..))
+    }
+}
+
+/// Iterator which loads the nibbles in an `AtomicNibSlice`.
+#[derive(Clone, Debug)]
+pub struct AtomicNibbles<'a, O: NibOrder = HiFirst> {
+    slice: AtomicNibSlice<'a, O>,
+    order: Ordering,
+    front: usize,
+    back: usize,
+}
+impl<'a, O: NibOrder> Iterator for AtomicNibbles<'a, O> {
+    type Item = u4lo;
+    fn next(&mut self) -> Option<u4lo> {
+        if self.front < self.back {
+            self.front += 1;
+            Some(self.slice.load(self.front - 1, self.order))
+        } else {
+            None
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.back - self.front;
+        (len, Some(len))
+    }
+}
+impl<'a, O: NibOrder> DoubleEndedIterator for AtomicNibbles<'a, O> {
+    fn next_back(&mut self) -> Option<u4lo> {
+        if self.front < self.back {
+            self.back -= 1;
+            Some(self.slice.load(self.back, self.order))
+        } else {
+            None
+        }
+    }
+}
+impl<'a, O: NibOrder> ExactSizeIterator for AtomicNibbles<'a, O> {}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+    use super::*;
+    use order::LoFirst;
+
+    #[test]
+    fn cells() {
+        let byte = AtomicU8::new(0x12);
+        let hi = AtomicU4Hi::from_atomic(&byte);
+        let lo = AtomicU4Lo::from_atomic(&byte);
+        assert_eq!(hi.swap(u4lo::from_lo(0xA), Ordering::SeqCst).to_lo(), 1);
+        let (two, b, c) = (u4lo::from_lo(2), u4lo::from_lo(0xB), u4lo::from_lo(0xC));
+        let exchanged = lo.compare_exchange(two, b, Ordering::SeqCst, Ordering::SeqCst);
+        assert_eq!(exchanged.map(|nib| nib.to_lo()), Ok(2));
+        let exchanged = lo.compare_exchange(two, c, Ordering::SeqCst, Ordering::SeqCst);
+        assert_eq!(exchanged.map_err(|nib| nib.to_lo()), Err(0xB));
+        assert_eq!(byte.load(Ordering::SeqCst), 0xAB);
+        assert_eq!(hi.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |_: u4lo| None)
+            .map_err(|nib| nib.to_lo()), Err(0xA));
+    }
+
+    #[test]
+    fn slice() {
+        let bytes = [AtomicU8::new(0x01), AtomicU8::new(0x23), AtomicU8::new(0x45)];
+        let slice = AtomicNibSlice::from_atomics(&bytes).slice(1..5);
+        let (left, right) = slice.split_at(1);
+        left.store(0, u4lo::from_lo(0xF), Ordering::Relaxed);
+        right.store(2, u4lo::from_lo(0xE), Ordering::Relaxed);
+        assert_eq!(slice.get::<u4lo>(4, Ordering::Relaxed), None);
+        let nibs: Vec<u8> = slice.nibbles(Ordering::Relaxed).map(|nib| nib.to_lo()).collect();
+        assert_eq!(nibs, [0xF, 2, 3, 0xE]);
+        let bytes: Vec<u8> = bytes.iter().map(|b| b.load(Ordering::Relaxed)).collect();
+        assert_eq!(bytes, [0x0F, 0x23, 0xE5]);
+
+        let bytes = [AtomicU8::new(0x10), AtomicU8::new(0x32)];
+        let slice = AtomicNibSlice::<LoFirst>::from_ordered_atomics(&bytes);
+        let nibs: Vec<u8> = slice.nibbles(Ordering::Relaxed).map(|nib| nib.to_lo()).collect();
+        assert_eq!(nibs, [0, 1, 2, 3]);
+        slice.slice(1..).store(2, u4lo::from_lo(0xF), Ordering::Relaxed);
+        assert_eq!(bytes[1].load(Ordering::Relaxed), 0xF2);
+    }
+
+    #[test]
+    fn no_tearing() {
+        let bytes: Arc<Vec<AtomicU8>> = Arc::new((0..4).map(|_| AtomicU8::new(0)).collect());
+        let threads: Vec<_> = (0..8).map(|idx| {
+            let bytes = bytes.clone();
+            thread::spawn(move || {
+                let slice = AtomicNibSlice::from_atomics(&bytes);
+                for _ in 0..15 {
+                    slice.fetch_update(idx, Ordering::SeqCst, Ordering::SeqCst, |nib: u4lo| {
+                        Some(u4lo::from_lo(nib.to_lo() + 1))
+                    }).unwrap();
+                }
+            })
+        }).collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert!(bytes.iter().all(|b| b.load(Ordering::SeqCst) == 0xFF));
+    }
+}
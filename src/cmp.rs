@@ -1,15 +1,23 @@
 use arrayvec::Array;
 
-use array::{NibArray, NibArrayEven, NibArrayOdd, NibArrayVec};
+use array::{NibArray, NibArrayEven, NibArrayOdd, NibArrayVec, NibArr, NibArrLen, NibLen};
 use base::{u4hi, u4lo, u4};
 use core::{cmp, hash};
 use pair::u4x2;
 use slice::{NibSliceFull, NibSliceNoL, NibSliceNoR, NibSliceNoBoth};
 use slice::{NibSliceAligned, NibSliceAlignedMut, NibSliceUnaligned, NibSliceUnalignedMut};
 use slice::{NibSliceEvenMut, NibSliceEven, NibSliceOdd, NibSliceOddMut};
-use slice::{NibSliceExt, NibSlice, NibSliceMut};
+use slice::{NibSliceExt, NibSlice, NibSliceMut, NibSliceRef, NibSliceRefMut, NibKey};
 use slice::private::Sealed;
+#[cfg(feature = "std")]
 use vec::NibVec;
+#[cfg(feature = "std")]
+use order::NibOrder;
+#[cfg(feature = "std")]
+use small::SmallNibVec;
+#[cfg(feature = "std")]
+use owned::{NibBox, NibRc, NibArc, NibCow};
+use storage::{NibBytes, NibStorage};
 
 macro_rules! do_impl {
     ($($t:ident)*) => {
@@ -115,10 +123,12 @@ impl Ord for u4x2 {
 
 macro_rules! do_slice {
     ($(
+        $(#[$attr:meta])*
         ($($gen:tt)*)
         ($t:path)
     ;)*) => {
         $(
+            $(#[$attr])*
             impl<$($gen)*, Rhs: ?Sized + NibSliceExt> PartialEq<Rhs> for $t {
                 fn eq(&self, rhs: &Rhs) -> bool {
                     if self.len() != rhs.len() {
@@ -135,6 +145,7 @@ macro_rules! do_slice {
                     }
                 }
             }
+            $(#[$attr])*
             impl<$($gen)*, Rhs: ?Sized + NibSliceExt> PartialOrd<Rhs> for $t {
                 fn partial_cmp(&self, rhs: &Rhs) -> Option<cmp::Ordering> {
                     let cond =
@@ -147,12 +158,20 @@ macro_rules! do_slice {
                     }
                 }
             }
+            $(#[$attr])*
             impl<$($gen)*> hash::Hash for $t {
                 fn hash<H: hash::Hasher>(&self, state: &mut H) {
-                    self.decompose().hash(state)
+                    // hash the nibbles themselves so that equal slices hash equally, whatever
+                    // their alignment
+                    self.len().hash(state);
+                    for nib in self.nibbles() {
+                        nib.to_lo().hash(state);
+                    }
                 }
             }
+            $(#[$attr])*
             impl<$($gen)*> Eq for $t {}
+            $(#[$attr])*
             impl<$($gen)*> Ord for $t {
                 fn cmp(&self, rhs: &$t) -> cmp::Ordering {
                     self.decompose().cmp(&rhs.decompose())
@@ -177,9 +196,57 @@ do_slice! {
     ('a) (NibSliceEvenMut<'a>);
     ('a) (NibSliceOdd<'a>);
     ('a) (NibSliceOddMut<'a>);
+    ('a) (NibSliceRef<'a>);
+    ('a) (NibSliceRefMut<'a>);
     (A: Array<Item = u4x2>) (NibArrayOdd<A>);
     (A: Array<Item = u4x2>) (NibArrayEven<A>);
     (A: Array<Item = u4x2>) (NibArray<A>);
     (A: Array<Item = u4x2>) (NibArrayVec<A>);
-    ('unused) (NibVec);
+    #[cfg(feature = "std")]
+    (O: NibOrder) (NibVec<O>);
+    #[cfg(feature = "std")]
+    (A: Array<Item = u4x2>) (SmallNibVec<A>);
+    (B: NibStorage) (NibBytes<B>);
+    #[cfg(feature = "std")]
+    ('unused) (NibBox);
+    #[cfg(feature = "std")]
+    ('unused) (NibRc);
+    #[cfg(feature = "std")]
+    ('unused) (NibArc);
+    #[cfg(feature = "std")]
+    ('a) (NibCow<'a>);
+}
+
+impl<'a> PartialEq for dyn NibKey + 'a {
+    fn eq(&self, rhs: &(dyn NibKey + 'a)) -> bool {
+        self.as_key_slice() == rhs.as_key_slice()
+    }
+}
+impl<'a> Eq for dyn NibKey + 'a {}
+impl<'a> hash::Hash for dyn NibKey + 'a {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.as_key_slice().hash(state)
+    }
+}
+
+impl<const N: usize, Rhs: ?Sized + NibSliceExt> PartialEq<Rhs> for NibArr<N> where NibLen<N>: NibArrLen {
+    fn eq(&self, rhs: &Rhs) -> bool {
+        self.into_generic().eq(rhs)
+    }
+}
+impl<const N: usize, Rhs: ?Sized + NibSliceExt> PartialOrd<Rhs> for NibArr<N> where NibLen<N>: NibArrLen {
+    fn partial_cmp(&self, rhs: &Rhs) -> Option<cmp::Ordering> {
+        self.into_generic().partial_cmp(rhs)
+    }
+}
+impl<const N: usize> hash::Hash for NibArr<N> where NibLen<N>: NibArrLen {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.into_generic().hash(state)
+    }
+}
+impl<const N: usize> Eq for NibArr<N> where NibLen<N>: NibArrLen {}
+impl<const N: usize> Ord for NibArr<N> where NibLen<N>: NibArrLen {
+    fn cmp(&self, rhs: &Self) -> cmp::Ordering {
+        self.into_generic().cmp(&rhs.into_generic())
+    }
 }
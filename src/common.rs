@@ -1,5 +1,7 @@
-use base::u4;
-use pair::{u4x2, U4Cell};
+use core::ops::{Bound, Range, RangeBounds};
+use base::{u4, u4lo};
+use pair::u4x2;
+use order::{HiFirst, NibOrder};
 
 #[inline(always)] pub(crate) fn has_lower(byte: u8) -> bool { byte & 0b0000_1111 != 0 }
 #[inline(always)] pub(crate) fn has_higher(byte: u8) -> bool { byte & 0b1111_0000 != 0 }
@@ -51,6 +53,10 @@ pub(crate) fn shift_left(slice: &mut [u4x2], nibidx: usize) {
     // shifting odd nibbles requires replacing lo without removing hi
     } else {
         *slice[byteidx].byte_mut() &= 0xF0;
+        // removing the very last nibble leaves nothing to shift in
+        if byteidx + 1 == bytelen {
+            return;
+        }
         *slice[byteidx].byte_mut() |= slice[byteidx + 1].hi().to_lo();
         byteidx + 1
     };
@@ -64,39 +70,312 @@ pub(crate) fn shift_left(slice: &mut [u4x2], nibidx: usize) {
     *slice[bytelen - 1].byte_mut() <<= 4;
 }
 
-pub(crate) fn set_nib<T: u4>(slice: &mut [u4x2], nibidx: usize, nib: T) {
-    let idx = nibidx >> 1;
-    if nibidx & 1 == 0 {
-        slice[idx].set_hi(nib);
-    } else {
-        slice[idx].set_lo(nib);
+/// Resolves a range of nibble indices against a slice of the given length.
+///
+/// # Panics
+///
+/// Panics if the range is out of bounds or its start is greater than its end.
+pub(crate) fn nib_range<R: RangeBounds<usize>>(range: R, len: usize) -> (usize, usize) {
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start + 1,
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => end + 1,
+        Bound::Excluded(&end) => end,
+        Bound::Unbounded => len,
+    };
+    assert!(start <= end, "nibble range starts at {} but ends at {}", start, end);
+    assert!(end <= len, "nibble range end {} out of range for slice of length {}", end, len);
+    (start, end)
+}
+
+/// Converts a range of nibble indices into the underlying pairs into the range of pairs covering
+/// them, along with whether the first pair's high-order and last pair's low-order nibbles are
+/// included.
+pub(crate) fn pair_range(start: usize, end: usize) -> (Range<usize>, bool, bool) {
+    (start >> 1..(end + 1) >> 1, start & 1 == 0, end & 1 == 0)
+}
+
+pub(crate) fn as_bytes(slice: &[u4x2]) -> &[u8] {
+    unsafe { &*(slice as *const [u4x2] as *const [u8]) }
+}
+
+pub(crate) fn as_bytes_mut(slice: &mut [u4x2]) -> &mut [u8] {
+    unsafe { &mut *(slice as *mut [u4x2] as *mut [u8]) }
+}
+
+pub(crate) fn as_pairs(slice: &[u8]) -> &[u4x2] {
+    unsafe { &*(slice as *const [u8] as *const [u4x2]) }
+}
+
+pub(crate) fn as_pairs_mut(slice: &mut [u8]) -> &mut [u4x2] {
+    unsafe { &mut *(slice as *mut [u8] as *mut [u4x2]) }
+}
+
+/// Swaps the two nibbles in every byte, a `u64` at a time.
+pub(crate) fn swap_nibbles(bytes: &mut [u8]) {
+    let mut chunks = bytes.chunks_exact_mut(8);
+    for chunk in &mut chunks {
+        let mut word = [0; 8];
+        word.copy_from_slice(chunk);
+        let word = u64::from_ne_bytes(word);
+        let word = (word >> 4) & 0x0F0F_0F0F_0F0F_0F0F | (word << 4) & 0xF0F0_F0F0_F0F0_F0F0;
+        chunk.copy_from_slice(&word.to_ne_bytes());
+    }
+    for byte in chunks.into_remainder() {
+        *byte = *byte >> 4 | *byte << 4;
     }
 }
 
-pub(crate) fn get_nib<T: u4>(slice: &[u4x2], nibidx: usize) -> T {
-    let idx = nibidx >> 1;
-    if nibidx & 1 == 0 {
-        T::from_hi(slice[idx].hi().to_hi())
+/// Fills `dst` with the bytes of `src` shifted left by one nibble.
+///
+/// `src` must be exactly one byte longer than `dst`, and this works a `u64` at a time.
+fn shift_copy(dst: &mut [u8], src: &[u8]) {
+    debug_assert_eq!(dst.len() + 1, src.len());
+    let mut i = 0;
+    while i + 8 < src.len() {
+        let mut word = [0; 8];
+        word.copy_from_slice(&src[i..i + 8]);
+        let word = u64::from_be_bytes(word) << 4 | u64::from(src[i + 8] >> 4);
+        dst[i..i + 8].copy_from_slice(&word.to_be_bytes());
+        i += 8;
+    }
+    for i in i..dst.len() {
+        dst[i] = src[i] << 4 | src[i + 1] >> 4;
+    }
+}
+
+/// Copies `len` nibbles from `src`, starting at nibble `src_start`, into `dst`, starting at nibble
+/// `dst_start`.
+///
+/// Whole pairs are copied at once if both sides have the same alignment, and shifted a word at a
+/// time otherwise.
+pub(crate) fn copy_nibs(dst: &mut [u4x2], dst_start: usize, src: &[u4x2], src_start: usize, len: usize) {
+    let (mut dst_start, mut src_start, mut len) = (dst_start, src_start, len);
+    if len == 0 {
+        return;
+    }
+
+    // get the destination aligned first
+    if dst_start & 1 == 1 {
+        let nib: u4lo = get_nib(src, src_start);
+        set_nib(dst, dst_start, nib);
+        dst_start += 1;
+        src_start += 1;
+        len -= 1;
+    }
+
+    let pairs = len >> 1;
+    let dst_idx = dst_start >> 1;
+    let src_idx = src_start >> 1;
+    if src_start & 1 == 0 {
+        dst[dst_idx..dst_idx + pairs].copy_from_slice(&src[src_idx..src_idx + pairs]);
+    } else if pairs > 0 {
+        shift_copy(
+            &mut as_bytes_mut(dst)[dst_idx..dst_idx + pairs],
+            &as_bytes(src)[src_idx..src_idx + pairs + 1],
+        );
+    }
+
+    if len & 1 == 1 {
+        let nib: u4lo = get_nib(src, src_start + len - 1);
+        set_nib(dst, dst_start + len - 1, nib);
+    }
+}
+
+/// Moves `len` nibbles within `slice` from nibble `src` to nibble `dst`.
+///
+/// Like `memmove`, the two regions may overlap.
+pub(crate) fn move_nibs(slice: &mut [u4x2], src: usize, dst: usize, len: usize) {
+    if len == 0 || src == dst {
+        return;
+    }
+
+    // the partial pairs at either end of the destination are written last
+    let head = dst & 1;
+    let tail = (dst + len) & 1;
+    let first: u4lo = get_nib(slice, src);
+    let last: u4lo = get_nib(slice, src + len - 1);
+
+    let pairs = (len - head - tail) >> 1;
+    let dst_idx = (dst + head) >> 1;
+    let src_start = src + head;
+    let src_idx = src_start >> 1;
+    if src_start & 1 == 0 {
+        slice.copy_within(src_idx..src_idx + pairs, dst_idx);
     } else {
-        T::from_lo(slice[idx].lo().to_lo())
+        // each destination pair straddles two source pairs; walk away from the overlap
+        let bytes = as_bytes_mut(slice);
+        if dst_idx > src_idx {
+            for i in (0..pairs).rev() {
+                bytes[dst_idx + i] = (bytes[src_idx + i] << 4) | (bytes[src_idx + i + 1] >> 4);
+            }
+        } else {
+            for i in 0..pairs {
+                bytes[dst_idx + i] = (bytes[src_idx + i] << 4) | (bytes[src_idx + i + 1] >> 4);
+            }
+        }
+    }
+
+    if head == 1 {
+        set_nib(slice, dst, first);
+    }
+    if tail == 1 {
+        set_nib(slice, dst + len - 1, last);
+    }
+}
+
+/// Fills `len` nibbles in `slice`, starting at nibble `start`, with the given nibble.
+pub(crate) fn fill_nibs(slice: &mut [u4x2], start: usize, len: usize, nib: u4lo) {
+    let (mut start, mut len) = (start, len);
+    if len == 0 {
+        return;
+    }
+    if start & 1 == 1 {
+        set_nib(slice, start, nib);
+        start += 1;
+        len -= 1;
+    }
+    let idx = start >> 1;
+    for pair in &mut as_bytes_mut(slice)[idx..idx + (len >> 1)] {
+        *pair = nib.to_hi() | nib.to_lo();
+    }
+    if len & 1 == 1 {
+        set_nib(slice, start + len - 1, nib);
     }
 }
 
-pub(crate) fn get_nib_ref(slice: &[u4x2], nibidx: usize) -> &u4 {
+/// Reverses the nibbles in a slice, leaving any omitted nibbles in place.
+///
+/// The pairs are reversed and swapped a whole byte at a time; if that leaves the nibbles one
+/// nibble off from where they started, they're shifted back into place.
+pub(crate) fn reverse_nibs(slice: &mut [u4x2], has_left_hi: bool, has_right_lo: bool) {
+    let bytelen = slice.len();
+    if bytelen == 0 {
+        return;
+    }
+    let left = *slice[0].hi();
+    let right = *slice[bytelen - 1].lo();
+
+    slice.reverse();
+    for pair in slice.iter_mut() {
+        pair.swap_pairs();
+    }
+    match (has_left_hi, has_right_lo) {
+        (true, false) => shift_left(slice, 0),
+        (false, true) => shift_right(slice, 0),
+        _ => (),
+    }
+
+    if !has_left_hi {
+        slice[0].set_hi(left);
+    }
+    if !has_right_lo {
+        slice[bytelen - 1].set_lo(right);
+    }
+}
+
+const ONES: u64 = 0x1111_1111_1111_1111;
+const LOWS: u64 = 0x7777_7777_7777_7777;
+
+/// Sets the high bit of every nibble in the word which is zero, and clears all other bits.
+///
+/// Unlike the usual subtraction trick, no carries cross nibble boundaries, so there are no false
+/// positives.
+#[inline]
+fn zero_nibbles(word: u64) -> u64 {
+    !(((word & LOWS) + LOWS) | word | LOWS)
+}
+
+#[inline]
+fn read_word(bytes: &[u8]) -> u64 {
+    let mut word = [0; 8];
+    word.copy_from_slice(&bytes[..8]);
+    u64::from_be_bytes(word)
+}
+
+/// Finds the first occurrence of a nibble between the nibble indices `start` and `end`.
+///
+/// This checks sixteen nibbles at a time.
+pub(crate) fn find_nib(slice: &[u4x2], start: usize, end: usize, nib: u4lo) -> Option<usize> {
+    let mut idx = start;
+    if idx < end && idx & 1 == 1 {
+        if get_nib::<u4lo>(slice, idx) == nib {
+            return Some(idx);
+        }
+        idx += 1;
+    }
+
+    let bytes = as_bytes(slice);
+    let pattern = ONES * u64::from(nib.to_lo());
+    while idx + 16 <= end {
+        let zeros = zero_nibbles(read_word(&bytes[idx >> 1..]) ^ pattern);
+        if zeros != 0 {
+            return Some(idx + (zeros.leading_zeros() >> 2) as usize);
+        }
+        idx += 16;
+    }
+
+    (idx..end).find(|&idx| get_nib::<u4lo>(slice, idx) == nib)
+}
+
+/// Finds the last occurrence of a nibble between the nibble indices `start` and `end`.
+///
+/// This checks sixteen nibbles at a time.
+pub(crate) fn rfind_nib(slice: &[u4x2], start: usize, end: usize, nib: u4lo) -> Option<usize> {
+    let mut idx = end;
+    if idx > start && idx & 1 == 1 {
+        if get_nib::<u4lo>(slice, idx - 1) == nib {
+            return Some(idx - 1);
+        }
+        idx -= 1;
+    }
+
+    let bytes = as_bytes(slice);
+    let pattern = ONES * u64::from(nib.to_lo());
+    while idx >= start + 16 {
+        let zeros = zero_nibbles(read_word(&bytes[(idx - 16) >> 1..]) ^ pattern);
+        if zeros != 0 {
+            return Some(idx - 1 - (zeros.trailing_zeros() >> 2) as usize);
+        }
+        idx -= 16;
+    }
+
+    (start..idx).rev().find(|&idx| get_nib::<u4lo>(slice, idx) == nib)
+}
+
+pub(crate) fn set_nib<T: u4>(slice: &mut [u4x2], nibidx: usize, nib: T) {
+    set_ordered_nib::<HiFirst, T>(slice, nibidx, nib)
+}
+
+pub(crate) fn get_nib<T: u4>(slice: &[u4x2], nibidx: usize) -> T {
+    get_ordered_nib::<HiFirst, T>(slice, nibidx)
+}
+
+/// Whether the nibble at the given index of some pairs is the high-order nibble of its pair, when
+/// the pairs are packed in the given order.
+#[inline(always)]
+pub(crate) fn is_hi<O: NibOrder>(nibidx: usize) -> bool {
+    (nibidx & 1 == 0) != O::LO_FIRST
+}
+
+pub(crate) fn set_ordered_nib<O: NibOrder, T: u4>(slice: &mut [u4x2], nibidx: usize, nib: T) {
     let idx = nibidx >> 1;
-    if nibidx & 1 == 0 {
-        slice[idx].hi()
+    if is_hi::<O>(nibidx) {
+        slice[idx].set_hi(nib);
     } else {
-        slice[idx].lo()
+        slice[idx].set_lo(nib);
     }
 }
 
-pub(crate) fn get_nib_mut(slice: &mut [u4x2], nibidx: usize) -> &U4Cell {
+pub(crate) fn get_ordered_nib<O: NibOrder, T: u4>(slice: &[u4x2], nibidx: usize) -> T {
     let idx = nibidx >> 1;
-    if nibidx & 1 == 0 {
-        slice[idx].hi_mut()
+    if is_hi::<O>(nibidx) {
+        T::from_hi(slice[idx].hi().to_hi())
     } else {
-        slice[idx].lo_mut()
+        T::from_lo(slice[idx].lo().to_lo())
     }
 }
 
@@ -128,3 +407,35 @@ impl ToLo for usize {
         *self as u8
     }
 }
+
+/// Deterministic pseudo-random numbers, for randomised tests and rope priorities.
+#[cfg(any(test, feature = "std"))]
+#[derive(Clone, Debug)]
+pub(crate) struct XorShift(pub(crate) u64);
+#[cfg(any(test, feature = "std"))]
+impl XorShift {
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+#[cfg(test)]
+impl XorShift {
+    /// Random number in `0..n`.
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    /// Random nibble.
+    pub(crate) fn nib(&mut self) -> u8 {
+        (self.next() & 0xF) as u8
+    }
+
+    /// Random range within `0..len`.
+    pub(crate) fn range(&mut self, len: usize) -> (usize, usize) {
+        let (a, b) = (self.below(len + 1), self.below(len + 1));
+        if a < b { (a, b) } else { (b, a) }
+    }
+}
@@ -0,0 +1,249 @@
+//! Types for double-ended queues of nibbles.
+use std::cmp;
+use std::iter::{Chain, FromIterator};
+use base::u4;
+use pair::u4x2;
+use iter::Nibbles;
+use slice::NibSlice;
+use common::{copy_nibs, get_nib, set_nib};
+
+/// A `VecDeque` of nibbles.
+///
+/// Nibbles are stored in a ring buffer of pairs, so both ends can be pushed and popped without
+/// shifting the rest of the queue.
+#[derive(Clone, Default)]
+pub struct NibVecDeque {
+    buf: Vec<u4x2>,
+    head: usize,
+    len: usize,
+}
+impl NibVecDeque {
+    /// Creates an empty `NibVecDeque`.
+    pub fn new() -> Self {
+        NibVecDeque { buf: Vec::new(), head: 0, len: 0 }
+    }
+
+    /// Creates an empty queue with space for at least `capacity` nibbles.
+    pub fn with_capacity(capacity: usize) -> Self {
+        NibVecDeque { buf: vec![u4x2::from_byte(0); (capacity + 1) >> 1], head: 0, len: 0 }
+    }
+
+    /// Number of nibbles in the queue.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// How many nibbles can be stored in the queue without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.buf.len() << 1
+    }
+
+    /// Converts an index into the queue into an index into the buffer.
+    fn wrap(&self, idx: usize) -> usize {
+        let idx = self.head + idx;
+        let cap = self.capacity();
+        if idx >= cap { idx - cap } else { idx }
+    }
+
+    /// Makes room for at least one more nibble, moving the contents to the start of the buffer.
+    fn grow(&mut self) {
+        let pairs = cmp::max(4, self.buf.len() << 1);
+        let mut buf = vec![u4x2::from_byte(0); pairs];
+        let first = cmp::min(self.len, self.capacity() - self.head);
+        copy_nibs(&mut buf, 0, &self.buf, self.head, first);
+        copy_nibs(&mut buf, first, &self.buf, 0, self.len - first);
+        self.buf = buf;
+        self.head = 0;
+    }
+
+    /// Gets the nibble at the given index, counting from the front.
+    pub fn get<T: u4>(&self, idx: usize) -> Option<T> {
+        if idx < self.len {
+            Some(get_nib(&self.buf, self.wrap(idx)))
+        } else {
+            None
+        }
+    }
+
+    /// Sets the nibble at the given index, counting from the front.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx >= len`.
+    pub fn set<T: u4>(&mut self, idx: usize, nib: T) {
+        assert!(idx < self.len, "index {} out of range for queue of length {}", idx, self.len);
+        let idx = self.wrap(idx);
+        set_nib(&mut self.buf, idx, nib);
+    }
+
+    /// Gets the nibble at the front of the queue.
+    pub fn front<T: u4>(&self) -> Option<T> {
+        self.get(0)
+    }
+
+    /// Gets the nibble at the back of the queue.
+    pub fn back<T: u4>(&self) -> Option<T> {
+        self.len.checked_sub(1).and_then(|idx| self.get(idx))
+    }
+
+    /// Pushes a nibble onto the back of the queue.
+    pub fn push_back<T: u4>(&mut self, nib: T) {
+        if self.len == self.capacity() {
+            self.grow();
+        }
+        let idx = self.wrap(self.len);
+        set_nib(&mut self.buf, idx, nib);
+        self.len += 1;
+    }
+
+    /// Pushes a nibble onto the front of the queue.
+    pub fn push_front<T: u4>(&mut self, nib: T) {
+        if self.len == self.capacity() {
+            self.grow();
+        }
+        self.head = self.wrap(self.capacity() - 1);
+        set_nib(&mut self.buf, self.head, nib);
+        self.len += 1;
+    }
+
+    /// Removes a nibble from the back of the queue.
+    pub fn pop_back<T: u4>(&mut self) -> Option<T> {
+        let ret = self.back()?;
+        self.len -= 1;
+        Some(ret)
+    }
+
+    /// Removes a nibble from the front of the queue.
+    pub fn pop_front<T: u4>(&mut self) -> Option<T> {
+        let ret = self.front()?;
+        self.head = self.wrap(1);
+        self.len -= 1;
+        Some(ret)
+    }
+
+    /// Clears the queue, removing all nibbles.
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+
+    /// Gets the contents of the queue as two slices, which together hold the nibbles in order.
+    ///
+    /// The second slice is only nonempty if the contents wrap around the end of the buffer.
+    pub fn as_slices(&self) -> (NibSlice, NibSlice) {
+        let all = NibSlice::from_parts(&self.buf, true, true);
+        let first = cmp::min(self.len, self.capacity() - self.head);
+        (all.sub(self.head, self.head + first), all.sub(0, self.len - first))
+    }
+
+    /// Iterator over the nibbles in the queue, from front to back.
+    pub fn nibbles(&self) -> Chain<Nibbles, Nibbles> {
+        let (first, second) = self.as_slices();
+        first.into_nibbles().chain(second.into_nibbles())
+    }
+}
+impl<T: u4> FromIterator<T> for NibVecDeque {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut deque = Self::new();
+        deque.extend(iter);
+        deque
+    }
+}
+impl<'a> FromIterator<&'a u4> for NibVecDeque {
+    fn from_iter<I: IntoIterator<Item = &'a u4>>(iter: I) -> Self {
+        let mut deque = Self::new();
+        deque.extend(iter);
+        deque
+    }
+}
+impl<T: u4> Extend<T> for NibVecDeque {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for nib in iter {
+            self.push_back(nib);
+        }
+    }
+}
+impl<'a> Extend<&'a u4> for NibVecDeque {
+    fn extend<I: IntoIterator<Item = &'a u4>>(&mut self, iter: I) {
+        for nib in iter {
+            self.push_back(nib.to_u4lo());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use base::u4lo;
+    use super::*;
+    use common::XorShift;
+    use slice::NibSliceExt;
+
+    fn nibs(deque: &NibVecDeque) -> Vec<u8> {
+        deque.nibbles().map(|nib| nib.to_lo()).collect()
+    }
+
+    #[test]
+    fn wrap() {
+        let mut deque = NibVecDeque::with_capacity(8);
+        for i in 0..6 {
+            deque.push_back(u4lo::from_lo(i));
+        }
+        for _ in 0..5 {
+            deque.pop_front::<u4lo>();
+        }
+        for i in 6..11 {
+            deque.push_back(u4lo::from_lo(i));
+        }
+        assert_eq!(deque.capacity(), 8);
+        let (first, second) = deque.as_slices();
+        let first: Vec<u8> = first.nibbles().map(|nib| nib.to_lo()).collect();
+        let second: Vec<u8> = second.nibbles().map(|nib| nib.to_lo()).collect();
+        assert_eq!((first, second), (vec![5, 6, 7], vec![8, 9, 10]));
+
+        deque.push_front(u4lo::from_lo(4));
+        deque.push_front(u4lo::from_lo(3));
+        deque.push_front(u4lo::from_lo(2));
+        assert_eq!(deque.capacity(), 16);
+        assert_eq!(nibs(&deque), [2, 3, 4, 5, 6, 7, 8, 9, 10]);
+        assert_eq!(deque.nibbles().size_hint(), (9, Some(9)));
+        assert_eq!(deque.nibbles().rev().next().map(|nib| nib.to_lo()), Some(10));
+    }
+
+    #[test]
+    fn model() {
+        let mut rng = XorShift(0xd1b5_4a32_d192_ed03);
+        let mut deque = NibVecDeque::new();
+        let mut model = VecDeque::new();
+        for _ in 0..2000 {
+            match rng.below(5) {
+                0 => {
+                    let nib = rng.nib();
+                    deque.push_back(u4lo::from_lo(nib));
+                    model.push_back(nib);
+                }
+                1 => {
+                    let nib = rng.nib();
+                    deque.push_front(u4lo::from_lo(nib));
+                    model.push_front(nib);
+                }
+                2 => assert_eq!(deque.pop_back::<u4lo>().map(|nib| nib.to_lo()), model.pop_back()),
+                3 => assert_eq!(deque.pop_front::<u4lo>().map(|nib| nib.to_lo()), model.pop_front()),
+                _ => if !model.is_empty() {
+                    let (idx, nib) = (rng.below(model.len()), rng.nib());
+                    deque.set(idx, u4lo::from_lo(nib));
+                    model[idx] = nib;
+                },
+            }
+            assert_eq!(deque.len(), model.len());
+            assert_eq!(nibs(&deque), model.iter().cloned().collect::<Vec<_>>());
+            let (first, second) = deque.as_slices();
+            assert_eq!(first.len() + second.len(), model.len());
+        }
+    }
+}
@@ -1,17 +1,32 @@
 use core::fmt;
 use core::str::FromStr;
+use core::sync::atomic::Ordering;
 
 use arrayvec::{Array};
 
 use base::{u4, u4hi, u4lo};
 use base::{ParseNibbleError};
 use pair::u4x2;
+#[cfg(feature = "std")]
+use signature::Signature;
+#[cfg(feature = "std")]
 use vec::NibVec;
-use array::{NibArrayVec, NibArray, NibArrayOdd, NibArrayEven};
+use order::{NibOrder, LoFirst};
+#[cfg(feature = "std")]
+use deque::NibVecDeque;
+#[cfg(feature = "std")]
+use wide::WideNibVec;
+#[cfg(feature = "std")]
+use small::SmallNibVec;
+#[cfg(feature = "std")]
+use owned::{NibBox, NibRc, NibArc, NibCow};
+use storage::{NibBytes, NibStorage};
+use atomic::{AtomicU4Hi, AtomicU4Lo, AtomicNibSlice};
+use array::{NibArrayVec, NibArray, NibArrayOdd, NibArrayEven, NibArr, NibArrLen, NibLen};
 use slice::{NibSliceFull, NibSliceNoL, NibSliceNoR, NibSliceNoBoth};
 use slice::{NibSliceAligned, NibSliceAlignedMut, NibSliceUnaligned, NibSliceUnalignedMut};
 use slice::{NibSliceEven, NibSliceEvenMut, NibSliceOdd, NibSliceOddMut};
-use slice::{NibSlice, NibSliceMut, NibSliceExt};
+use slice::{NibSlice, NibSliceMut, NibSliceExt, NibSliceMutExt, NibSliceCell, NibSliceRef, NibSliceRefMut};
 
 macro_rules! do_nibble {
     ($($t:ident)*) => {
@@ -96,18 +111,30 @@ impl fmt::Display for u4x2 {
     }
 }
 
+#[cfg(feature = "std")]
 impl NibVec {
     pub(crate) fn try_push<T: u4>(&mut self, nib: T) -> Result<(), ParseNibbleError> {
-        Ok(self.push(nib))
+        self.push(nib);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<A: Array<Item = u4x2>> SmallNibVec<A> {
+    pub(crate) fn try_push<T: u4>(&mut self, nib: T) -> Result<(), ParseNibbleError> {
+        self.push(nib);
+        Ok(())
     }
 }
 
 macro_rules! do_slice {
     ($(
+        $(#[$attr:meta])*
         ($($gen:tt)*)
         ($t:ty)
     ;)*) => {
         $(
+            $(#[$attr])*
             impl<$($gen)*> fmt::Binary for $t {
                 fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
                     // TODO: remove allocation here
@@ -118,6 +145,7 @@ macro_rules! do_slice {
                     f.pad_integral(true, "0b", &s)
                 }
             }
+            $(#[$attr])*
             impl<$($gen)*> fmt::LowerHex for $t {
                 fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
                     // TODO: remove allocation here
@@ -127,6 +155,7 @@ macro_rules! do_slice {
                     f.pad_integral(true, "0x", &s)
                 }
             }
+            $(#[$attr])*
             impl<$($gen)*> fmt::UpperHex for $t {
                 fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
                     // TODO: remove allocation here
@@ -136,6 +165,7 @@ macro_rules! do_slice {
                     f.pad_integral(true, "0x", &s)
                 }
             }
+            $(#[$attr])*
             impl<$($gen)*> fmt::Debug for $t {
                 fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
                     f.debug_list()
@@ -162,19 +192,197 @@ do_slice! {
     ('a) (NibSliceEvenMut<'a>);
     ('a) (NibSliceOdd<'a>);
     ('a) (NibSliceOddMut<'a>);
+    ('a) (NibSliceRef<'a>);
+    ('a) (NibSliceRefMut<'a>);
     (A: Array<Item = u4x2>) (NibArrayOdd<A>);
     (A: Array<Item = u4x2>) (NibArrayEven<A>);
     (A: Array<Item = u4x2>) (NibArray<A>);
+    (B: NibStorage) (NibBytes<B>);
+    #[cfg(feature = "std")]
+    () (NibBox);
+    #[cfg(feature = "std")]
+    () (NibRc);
+    #[cfg(feature = "std")]
+    () (NibArc);
+    #[cfg(feature = "std")]
+    ('a) (NibCow<'a>);
+    (A: Array<Item = u4x2>) (NibArrayVec<A>);
+    #[cfg(feature = "std")]
+    (O: NibOrder) (NibVec<O>);
+    #[cfg(feature = "std")]
+    (A: Array<Item = u4x2>) (SmallNibVec<A>);
+}
+
+impl<const N: usize> fmt::Binary for NibArr<N> where NibLen<N>: NibArrLen {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Binary::fmt(&self.into_generic(), f)
+    }
+}
+impl<const N: usize> fmt::LowerHex for NibArr<N> where NibLen<N>: NibArrLen {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::LowerHex::fmt(&self.into_generic(), f)
+    }
+}
+impl<const N: usize> fmt::UpperHex for NibArr<N> where NibLen<N>: NibArrLen {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::UpperHex::fmt(&self.into_generic(), f)
+    }
+}
+impl<const N: usize> fmt::Debug for NibArr<N> where NibLen<N>: NibArrLen {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.into_generic(), f)
+    }
+}
+impl<const N: usize> FromStr for NibArr<N> where NibLen<N>: NibArrLen {
+    type Err = ParseNibbleError;
+    fn from_str(s: &str) -> Result<Self, ParseNibbleError> {
+        if s.is_empty() && N != 0 {
+            return Err(ParseNibbleError::Empty);
+        }
+        let mut arr = Self::new();
+        let mut len = 0;
+        for c in s.chars() {
+            let nib = u4lo::from_digit(c).ok_or(ParseNibbleError::BadFormat)?;
+            match arr.get_mut(len) {
+                Some(cell) => cell.set_from_lo(nib),
+                None => return Err(ParseNibbleError::TooLarge),
+            }
+            len += 1;
+        }
+        if len == N {
+            Ok(arr)
+        } else {
+            Err(ParseNibbleError::BadFormat)
+        }
+    }
+}
+
+impl<A: Array<Item = u4x2>> NibArray<A> {
+    /// Converts an ASCII hex string into a nibble array.
+    ///
+    /// The string must fill the array exactly, or leave only one nibble empty.
+    pub fn from_ascii(s: &[u8]) -> Result<Self, ParseNibbleError> {
+        if s.is_empty() && A::capacity() != 0 {
+            return Err(ParseNibbleError::Empty);
+        }
+        NibArrayVec::from_ascii(s).and_then(NibArray::from_full)
+    }
+
+    /// Converts a hex string into a nibble array.
+    ///
+    /// The string must fill the array exactly, or leave only one nibble empty.
+    pub fn from_str(s: &str) -> Result<Self, ParseNibbleError> {
+        if s.is_empty() && A::capacity() != 0 {
+            return Err(ParseNibbleError::Empty);
+        }
+        NibArrayVec::from_str(s).and_then(NibArray::from_full)
+    }
+
+    fn from_full(vec: NibArrayVec<A>) -> Result<Self, ParseNibbleError> {
+        vec.into_even_array()
+            .map(NibArray::Even)
+            .or_else(|vec| vec.into_odd_array().map(NibArray::Odd))
+            .map_err(|_| ParseNibbleError::BadFormat)
+    }
+}
+impl<A: Array<Item = u4x2>> FromStr for NibArray<A> {
+    type Err = ParseNibbleError;
+    fn from_str(s: &str) -> Result<Self, ParseNibbleError> {
+        Self::from_str(s)
+    }
+}
+
+impl<'a, O: NibOrder> fmt::Debug for NibSliceCell<'a, O> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries(self.nibbles().map(|cell| cell.get_lo()))
+            .finish()
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Debug for NibVecDeque {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries(self.nibbles())
+            .finish()
+    }
+}
+
+impl<B: NibStorage> fmt::Debug for NibBytes<B, LoFirst> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries((0..self.len()).map(|idx| self.get_nib::<u4lo>(idx).unwrap_or_else(|| unreachable!())))
+            .finish()
+    }
+}
+
+impl fmt::Debug for AtomicU4Hi {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.load::<u4hi>(Ordering::SeqCst), f)
+    }
+}
+
+impl fmt::Debug for AtomicU4Lo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.load::<u4lo>(Ordering::SeqCst), f)
+    }
+}
+
+impl<'a, O: NibOrder> fmt::Debug for AtomicNibSlice<'a, O> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries(self.nibbles(Ordering::SeqCst))
+            .finish()
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Debug for WideNibVec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries((0..self.len()).map(|idx| self.get::<u4lo>(idx).unwrap_or_else(|| unreachable!())))
+            .finish()
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for i in 0..self.len() {
+            if i != 0 && i & 1 == 0 {
+                f.write_str(" ")?;
+            }
+            match self.get(i) {
+                Some(nib) => write!(f, "{:X}", nib)?,
+                None => f.write_str("?")?,
+            }
+        }
+        Ok(())
+    }
+}
+#[cfg(feature = "std")]
+impl fmt::Debug for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Signature({})", self)
+    }
+}
+#[cfg(feature = "std")]
+impl FromStr for Signature {
+    type Err = ParseNibbleError;
+    fn from_str(s: &str) -> Result<Self, ParseNibbleError> {
+        Self::from_str(s)
+    }
 }
 
 macro_rules! do_array {
     ($(
+        $(#[$attr:meta])*
         ($($gen:tt)*)
         ($t:path)
     ;)*) => {
-        do_slice! { $( ($($gen)*) ($t); )* }
-
         $(
+            $(#[$attr])*
             impl<$($gen)*> $t {
                 /// Converts an ASCII hex string into a nibble vector.
                 pub fn from_ascii(s: &[u8]) -> Result<Self, ParseNibbleError> {
@@ -197,6 +405,7 @@ macro_rules! do_array {
                 }
             }
 
+            $(#[$attr])*
             impl<$($gen)*> FromStr for $t {
                 type Err = ParseNibbleError;
                 fn from_str(s: &str) -> Result<Self, ParseNibbleError> {
@@ -209,5 +418,16 @@ macro_rules! do_array {
 
 do_array! {
     (A: Array<Item = u4x2>) (NibArrayVec<A>);
+    #[cfg(feature = "std")]
     () (NibVec);
+    #[cfg(feature = "std")]
+    (A: Array<Item = u4x2>) (SmallNibVec<A>);
+}
+
+#[cfg(feature = "std")]
+impl FromStr for NibVec<LoFirst> {
+    type Err = ParseNibbleError;
+    fn from_str(s: &str) -> Result<Self, ParseNibbleError> {
+        NibVec::from_str(s).map(NibVec::into_order)
+    }
 }
@@ -2,6 +2,9 @@
 use core::slice;
 use base::u4;
 use pair::{Iter, IterMut, U4Cell, u4x2};
+use common::as_bytes;
+use order::{HiFirst, NibOrder};
+use slice::{NibSlice, NibSliceCell, NibSliceExt};
 
 /// Iterator over pairs of nibbles in a slice.
 #[derive(Debug)]
@@ -180,7 +183,7 @@ impl<'a> ExactSizeIterator for Nibbles<'a> {
     fn len(&self) -> usize {
         let front = self.front.as_ref().map(ExactSizeIterator::len).unwrap_or(0);
         let back = self.back.as_ref().map(ExactSizeIterator::len).unwrap_or(0);
-        let middle = self.pairs.len() >> 1;
+        let middle = self.pairs.len() << 1;
         front + middle + back
     }
 }
@@ -251,7 +254,403 @@ impl<'a> ExactSizeIterator for NibblesMut<'a> {
     fn len(&self) -> usize {
         let front = self.front.as_ref().map(ExactSizeIterator::len).unwrap_or(0);
         let back = self.back.as_ref().map(ExactSizeIterator::len).unwrap_or(0);
-        let middle = self.pairs.len() >> 1;
+        let middle = self.pairs.len() << 1;
         front + middle + back
     }
 }
+
+/// Iterator over the cells for nibbles in a slice.
+#[derive(Clone, Debug)]
+pub struct NibbleCells<'a, O: NibOrder = HiFirst> {
+    slice: NibSliceCell<'a, O>,
+    start: usize,
+    end: usize,
+}
+impl<'a, O: NibOrder> NibbleCells<'a, O> {
+    pub(crate) fn new(slice: NibSliceCell<'a, O>) -> Self {
+        NibbleCells { slice, start: 0, end: slice.len() }
+    }
+}
+impl<'a, O: NibOrder> Iterator for NibbleCells<'a, O> {
+    type Item = &'a U4Cell;
+    fn next(&mut self) -> Option<&'a U4Cell> {
+        if self.start == self.end {
+            None
+        } else {
+            self.start += 1;
+            self.slice.cell(self.start - 1)
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.len();
+        (len, Some(len))
+    }
+}
+impl<'a, O: NibOrder> DoubleEndedIterator for NibbleCells<'a, O> {
+    fn next_back(&mut self) -> Option<&'a U4Cell> {
+        if self.start == self.end {
+            None
+        } else {
+            self.end -= 1;
+            self.slice.cell(self.end)
+        }
+    }
+}
+impl<'a, O: NibOrder> ExactSizeIterator for NibbleCells<'a, O> {
+    fn len(&self) -> usize {
+        self.end - self.start
+    }
+}
+
+macro_rules! do_chunks {
+    ($(
+        ($slice:ident)
+        $(#[$chunks_attr:meta])* ($chunks:ident)
+        $(#[$exact_attr:meta])* ($exact:ident)
+        $(#[$rchunks_attr:meta])* ($rchunks:ident)
+    ;)*) => {
+        $(
+            $(#[$chunks_attr])*
+            #[derive(Clone, Debug)]
+            pub struct $chunks<'a> {
+                slice: $slice<'a>,
+                size: usize,
+            }
+            impl<'a> $chunks<'a> {
+                pub(crate) fn new(slice: $slice<'a>, size: usize) -> Self {
+                    assert!(size != 0, "chunk size must be nonzero");
+                    $chunks { slice, size }
+                }
+            }
+            impl<'a> Iterator for $chunks<'a> {
+                type Item = $slice<'a>;
+                fn next(&mut self) -> Option<$slice<'a>> {
+                    let len = self.slice.len();
+                    if len == 0 {
+                        return None;
+                    }
+                    let mid = if len < self.size { len } else { self.size };
+                    let chunk = self.slice.sub(0, mid);
+                    self.slice = self.slice.sub(mid, len);
+                    Some(chunk)
+                }
+                fn size_hint(&self) -> (usize, Option<usize>) {
+                    let len = self.len();
+                    (len, Some(len))
+                }
+            }
+            impl<'a> DoubleEndedIterator for $chunks<'a> {
+                fn next_back(&mut self) -> Option<$slice<'a>> {
+                    let len = self.slice.len();
+                    if len == 0 {
+                        return None;
+                    }
+                    let rem = len % self.size;
+                    let mid = len - if rem == 0 { self.size } else { rem };
+                    let chunk = self.slice.sub(mid, len);
+                    self.slice = self.slice.sub(0, mid);
+                    Some(chunk)
+                }
+            }
+            impl<'a> ExactSizeIterator for $chunks<'a> {
+                fn len(&self) -> usize {
+                    (self.slice.len() + self.size - 1) / self.size
+                }
+            }
+
+            $(#[$exact_attr])*
+            #[derive(Clone, Debug)]
+            pub struct $exact<'a> {
+                slice: $slice<'a>,
+                rem: $slice<'a>,
+                size: usize,
+            }
+            impl<'a> $exact<'a> {
+                pub(crate) fn new(slice: $slice<'a>, size: usize) -> Self {
+                    assert!(size != 0, "chunk size must be nonzero");
+                    let len = slice.len();
+                    let mid = len - len % size;
+                    $exact { slice: slice.sub(0, mid), rem: slice.sub(mid, len), size }
+                }
+
+                /// The nibbles left over which do not fit into a chunk.
+                pub fn remainder(&self) -> $slice<'a> {
+                    self.rem
+                }
+            }
+            impl<'a> Iterator for $exact<'a> {
+                type Item = $slice<'a>;
+                fn next(&mut self) -> Option<$slice<'a>> {
+                    let len = self.slice.len();
+                    if len == 0 {
+                        return None;
+                    }
+                    let chunk = self.slice.sub(0, self.size);
+                    self.slice = self.slice.sub(self.size, len);
+                    Some(chunk)
+                }
+                fn size_hint(&self) -> (usize, Option<usize>) {
+                    let len = self.len();
+                    (len, Some(len))
+                }
+            }
+            impl<'a> DoubleEndedIterator for $exact<'a> {
+                fn next_back(&mut self) -> Option<$slice<'a>> {
+                    let len = self.slice.len();
+                    if len == 0 {
+                        return None;
+                    }
+                    let chunk = self.slice.sub(len - self.size, len);
+                    self.slice = self.slice.sub(0, len - self.size);
+                    Some(chunk)
+                }
+            }
+            impl<'a> ExactSizeIterator for $exact<'a> {
+                fn len(&self) -> usize {
+                    self.slice.len() / self.size
+                }
+            }
+
+            $(#[$rchunks_attr])*
+            #[derive(Clone, Debug)]
+            pub struct $rchunks<'a> {
+                slice: $slice<'a>,
+                size: usize,
+            }
+            impl<'a> $rchunks<'a> {
+                pub(crate) fn new(slice: $slice<'a>, size: usize) -> Self {
+                    assert!(size != 0, "chunk size must be nonzero");
+                    $rchunks { slice, size }
+                }
+            }
+            impl<'a> Iterator for $rchunks<'a> {
+                type Item = $slice<'a>;
+                fn next(&mut self) -> Option<$slice<'a>> {
+                    let len = self.slice.len();
+                    if len == 0 {
+                        return None;
+                    }
+                    let mid = len.saturating_sub(self.size);
+                    let chunk = self.slice.sub(mid, len);
+                    self.slice = self.slice.sub(0, mid);
+                    Some(chunk)
+                }
+                fn size_hint(&self) -> (usize, Option<usize>) {
+                    let len = self.len();
+                    (len, Some(len))
+                }
+            }
+            impl<'a> DoubleEndedIterator for $rchunks<'a> {
+                fn next_back(&mut self) -> Option<$slice<'a>> {
+                    let len = self.slice.len();
+                    if len == 0 {
+                        return None;
+                    }
+                    let rem = len % self.size;
+                    let mid = if rem == 0 { self.size } else { rem };
+                    let chunk = self.slice.sub(0, mid);
+                    self.slice = self.slice.sub(mid, len);
+                    Some(chunk)
+                }
+            }
+            impl<'a> ExactSizeIterator for $rchunks<'a> {
+                fn len(&self) -> usize {
+                    (self.slice.len() + self.size - 1) / self.size
+                }
+            }
+        )*
+    }
+}
+
+do_chunks! {
+    (NibSlice)
+        /// Iterator over chunks of nibbles in a slice, starting at the beginning.
+        (Chunks)
+        /// Iterator over chunks of exactly the same number of nibbles in a slice.
+        (ChunksExact)
+        /// Iterator over chunks of nibbles in a slice, starting at the end.
+        (RChunks)
+    ;
+    (NibSliceCell)
+        /// Mutable iterator over chunks of nibbles in a slice, starting at the beginning.
+        (ChunksMut)
+        /// Mutable iterator over chunks of exactly the same number of nibbles in a slice.
+        (ChunksExactMut)
+        /// Mutable iterator over chunks of nibbles in a slice, starting at the end.
+        (RChunksMut)
+    ;
+}
+
+/// Iterator over overlapping windows of nibbles in a slice.
+#[derive(Clone, Debug)]
+pub struct Windows<'a> {
+    slice: NibSlice<'a>,
+    size: usize,
+}
+impl<'a> Windows<'a> {
+    pub(crate) fn new(slice: NibSlice<'a>, size: usize) -> Self {
+        assert!(size != 0, "window size must be nonzero");
+        Windows { slice, size }
+    }
+}
+impl<'a> Iterator for Windows<'a> {
+    type Item = NibSlice<'a>;
+    fn next(&mut self) -> Option<NibSlice<'a>> {
+        let len = self.slice.len();
+        if len < self.size {
+            return None;
+        }
+        let window = self.slice.sub(0, self.size);
+        self.slice = self.slice.sub(1, len);
+        Some(window)
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.len();
+        (len, Some(len))
+    }
+}
+impl<'a> DoubleEndedIterator for Windows<'a> {
+    fn next_back(&mut self) -> Option<NibSlice<'a>> {
+        let len = self.slice.len();
+        if len < self.size {
+            return None;
+        }
+        let window = self.slice.sub(len - self.size, len);
+        self.slice = self.slice.sub(0, len - 1);
+        Some(window)
+    }
+}
+impl<'a> ExactSizeIterator for Windows<'a> {
+    fn len(&self) -> usize {
+        (self.slice.len() + 1).saturating_sub(self.size)
+    }
+}
+
+/// Iterator over non-overlapping occurrences of a nibble subslice, and their indices.
+#[derive(Clone, Debug)]
+pub struct MatchIndices<'a, 'b> {
+    haystack: NibSlice<'a>,
+    needle: NibSlice<'b>,
+    pos: usize,
+}
+impl<'a, 'b> MatchIndices<'a, 'b> {
+    pub(crate) fn new(haystack: NibSlice<'a>, needle: NibSlice<'b>) -> Self {
+        assert!(!needle.is_empty(), "needle must be nonempty");
+        MatchIndices { haystack, needle, pos: 0 }
+    }
+}
+impl<'a, 'b> Iterator for MatchIndices<'a, 'b> {
+    type Item = (usize, NibSlice<'a>);
+    fn next(&mut self) -> Option<(usize, NibSlice<'a>)> {
+        let idx = self.haystack.find_from(self.needle, self.pos)?;
+        self.pos = idx + self.needle.len();
+        Some((idx, self.haystack.sub(idx, self.pos)))
+    }
+}
+
+/// Iterator over subslices separated by a nibble subslice.
+#[derive(Clone, Debug)]
+pub struct Split<'a, 'b> {
+    haystack: NibSlice<'a>,
+    sep: NibSlice<'b>,
+    pos: usize,
+    finished: bool,
+}
+impl<'a, 'b> Split<'a, 'b> {
+    pub(crate) fn new(haystack: NibSlice<'a>, sep: NibSlice<'b>) -> Self {
+        assert!(!sep.is_empty(), "separator must be nonempty");
+        Split { haystack, sep, pos: 0, finished: false }
+    }
+}
+impl<'a, 'b> Iterator for Split<'a, 'b> {
+    type Item = NibSlice<'a>;
+    fn next(&mut self) -> Option<NibSlice<'a>> {
+        if self.finished {
+            return None;
+        }
+        let start = self.pos;
+        match self.haystack.find_from(self.sep, start) {
+            Some(idx) => {
+                self.pos = idx + self.sep.len();
+                Some(self.haystack.sub(start, idx))
+            }
+            None => {
+                self.finished = true;
+                Some(self.haystack.sub(start, self.haystack.len()))
+            }
+        }
+    }
+}
+
+/// Iterator over the realigned bytes of a nibble slice.
+///
+/// Bytes are shifted into place a `u64` at a time; only the last few bytes are realigned one by
+/// one.
+#[derive(Clone, Debug)]
+pub struct RealignedBytes<'a> {
+    bytes: &'a [u8],
+    start: usize,
+    end: usize,
+    word: u64,
+    buffered: usize,
+}
+impl<'a> RealignedBytes<'a> {
+    pub(crate) fn new(pairs: &'a [u4x2], start: usize, end: usize) -> Self {
+        RealignedBytes { bytes: as_bytes(pairs), start, end, word: 0, buffered: 0 }
+    }
+
+    /// Loads the next eight realigned bytes into the buffer.
+    fn fill(&mut self) {
+        let idx = self.start >> 1;
+        let mut word = [0; 8];
+        word.copy_from_slice(&self.bytes[idx..idx + 8]);
+        let word = u64::from_be_bytes(word);
+        self.word = if self.start & 1 == 0 {
+            word
+        } else {
+            word << 4 | u64::from(self.bytes[idx + 8] >> 4)
+        };
+        self.buffered = 8;
+        self.start += 16;
+    }
+}
+impl<'a> Iterator for RealignedBytes<'a> {
+    type Item = u8;
+    fn next(&mut self) -> Option<u8> {
+        if self.buffered == 0 && self.start + 16 <= self.end {
+            self.fill();
+        }
+        if self.buffered > 0 {
+            let byte = (self.word >> 56) as u8;
+            self.word <<= 8;
+            self.buffered -= 1;
+            return Some(byte);
+        }
+        if self.start >= self.end {
+            return None;
+        }
+        let idx = self.start >> 1;
+        let byte = if self.start & 1 == 0 {
+            self.bytes[idx]
+        } else if self.start + 1 < self.end {
+            self.bytes[idx] << 4 | self.bytes[idx + 1] >> 4
+        } else {
+            self.bytes[idx] << 4
+        };
+        self.start += 2;
+        if self.start > self.end {
+            Some(byte & 0xF0)
+        } else {
+            Some(byte)
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.len();
+        (len, Some(len))
+    }
+}
+impl<'a> ExactSizeIterator for RealignedBytes<'a> {
+    fn len(&self) -> usize {
+        self.buffered + ((self.end.saturating_sub(self.start) + 1) >> 1)
+    }
+}
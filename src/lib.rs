@@ -1,5 +1,7 @@
 //! This crate contains all sorts of types for dealing with nibbles, i.e. four-byte numbers.
 //! Curretly, only unsigned nibbles are supported.
+//!
+//! Fixed-size arrays of nibbles are available as `NibArr<N>`, for every `N` up to 128.
 #![cfg_attr(not(feature = "std"), no_std)]
 #![cfg_attr(not(feature = "std"), feature(alloc))]
 #![doc(html_root_url = "https://docs.charr.xyz/nibble/")]
@@ -17,15 +19,45 @@ mod fmt;
 mod num;
 mod ops;
 pub mod array;
+pub mod atomic;
 pub mod base;
+#[cfg(feature = "std")]
+pub mod deque;
 pub mod iter;
+pub mod order;
+#[cfg(feature = "std")]
+pub mod owned;
 pub mod pair;
+#[cfg(feature = "std")]
+pub mod rope;
 pub mod slice;
 #[cfg(feature = "std")]
+pub mod signature;
+#[cfg(feature = "std")]
+pub mod small;
+pub mod storage;
+#[cfg(feature = "std")]
 pub mod vec;
-pub use array::{NibArrayVec, NibArray};
+#[cfg(feature = "std")]
+pub mod wide;
+pub use array::{NibArrayVec, NibArray, NibArr};
+pub use atomic::{AtomicU4Hi, AtomicU4Lo, AtomicNibSlice};
 pub use base::{u4, u4hi, u4lo};
+#[cfg(feature = "std")]
+pub use owned::{NibBox, NibRc, NibArc, NibCow};
+#[cfg(feature = "std")]
+pub use deque::NibVecDeque;
+#[cfg(feature = "std")]
+pub use rope::NibRope;
+pub use order::{NibOrder, HiFirst, LoFirst};
 pub use pair::{u4x2, U4Cell, U4LoCell, U4HiCell};
-pub use slice::{NibSlice, NibSliceMut, NibSliceExt, NibSliceMutExt};
+pub use slice::{NibSlice, NibSliceMut, NibSliceExt, NibSliceMutExt, NibSliceRef, NibSliceRefMut, NibKey};
+#[cfg(feature = "std")]
+pub use signature::{Signature, SignatureSet};
+#[cfg(feature = "std")]
+pub use small::SmallNibVec;
+pub use storage::{NibBytes, NibStorage, NibStorageMut};
+#[cfg(feature = "std")]
+pub use vec::{NibVec, PadPolicy};
 #[cfg(feature = "std")]
-pub use vec::{NibVec};
+pub use wide::WideNibVec;
@@ -0,0 +1,69 @@
+//! Orders in which the two nibbles of a byte can be stored.
+//!
+//! Nibbles are always handled in the order they appear; the order only matters when converting
+//! to and from bytes. `NibVec<O>` keeps its pairs high-order nibble first and reorders the bytes
+//! whenever they cross that boundary, so its slices, iterators, insertion, removal and parsing
+//! work the same for every order.
+//!
+//! Borrowed bytes are a different matter. `NibSlice`, `NibSliceMut` and the other slice types,
+//! along with their iterators and the methods of `NibSliceExt` and `NibSliceMutExt`, always read
+//! bytes high-order nibble first, because they work on whole pairs at once. Bytes in another
+//! order can be viewed in place with `NibBytes::from_ordered`,
+//! `NibSliceCell::from_ordered_byte_cells` or `AtomicNibSlice::from_ordered_atomics`, which
+//! access one nibble at a time, or copied into a `NibVec` with `NibVec::from_ordered_bytes` to
+//! get the full slice API.
+use common::swap_nibbles;
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// An order for the two nibbles of a byte.
+pub trait NibOrder: private::Sealed {
+    /// Whether the low-order nibble of each byte comes first.
+    const LO_FIRST: bool;
+
+    /// Converts bytes in this order into high-first order, or back.
+    ///
+    /// This is its own inverse.
+    fn reorder(bytes: &mut [u8]);
+}
+
+/// The high-order nibble of each byte comes first, as in hex dumps and BCD.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HiFirst;
+impl private::Sealed for HiFirst {}
+impl NibOrder for HiFirst {
+    const LO_FIRST: bool = false;
+
+    #[inline(always)]
+    fn reorder(_: &mut [u8]) {}
+}
+
+/// The low-order nibble of each byte comes first, as in TBCD and 4bpp graphics.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LoFirst;
+impl private::Sealed for LoFirst {}
+impl NibOrder for LoFirst {
+    const LO_FIRST: bool = true;
+
+    #[inline]
+    fn reorder(bytes: &mut [u8]) {
+        swap_nibbles(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reorder() {
+        let mut bytes: Vec<u8> = (0..19).map(|i| i * 13).collect();
+        let expected: Vec<u8> = bytes.iter().map(|&b| b >> 4 | b << 4).collect();
+        LoFirst::reorder(&mut bytes);
+        assert_eq!(bytes, expected);
+        HiFirst::reorder(&mut bytes);
+        assert_eq!(bytes, expected);
+    }
+}
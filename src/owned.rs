@@ -0,0 +1,318 @@
+//! Owned and shared slices of nibbles.
+use std::borrow::Borrow;
+use std::rc::Rc;
+use std::slice as stdslice;
+use std::sync::Arc;
+use pair::u4x2;
+use slice::{self, NibSlice, NibSliceAligned, NibSliceAlignedMut, NibSliceExt};
+use slice::{NibKey, NibSliceFull, NibSliceNoR};
+use vec::NibVec;
+
+fn aligned(inner: &[u4x2], has_right_lo: bool) -> NibSliceAligned {
+    if has_right_lo {
+        NibSliceAligned::Even(NibSliceFull::from_slice(inner))
+    } else {
+        NibSliceAligned::Odd(NibSliceNoR::from_slice(inner))
+    }
+}
+
+/// A boxed slice of nibbles.
+#[derive(Clone)]
+pub struct NibBox {
+    inner: Box<[u4x2]>,
+    has_right_lo: bool,
+}
+impl NibBox {
+    /// Converts the box back into a vector, without reallocating.
+    pub fn into_vec(self) -> NibVec {
+        NibVec::from_parts(self.inner.into_vec(), self.has_right_lo)
+    }
+
+    /// Intreprets this box as a slice.
+    pub fn as_slice(&self) -> NibSliceAligned {
+        aligned(&self.inner, self.has_right_lo)
+    }
+
+    /// Intreprets this box as a mutable slice.
+    pub fn as_mut_slice(&mut self) -> NibSliceAlignedMut {
+        if self.has_right_lo {
+            NibSliceAlignedMut::Even(NibSliceFull::from_mut_slice(&mut self.inner))
+        } else {
+            NibSliceAlignedMut::Odd(NibSliceNoR::from_mut_slice(&mut self.inner))
+        }
+    }
+}
+impl From<NibVec> for NibBox {
+    fn from(vec: NibVec) -> Self {
+        let (inner, has_right_lo) = vec.into_parts();
+        NibBox { inner: inner.into_boxed_slice(), has_right_lo }
+    }
+}
+impl<'a> From<NibSlice<'a>> for NibBox {
+    fn from(slice: NibSlice<'a>) -> Self {
+        slice.to_vec().into()
+    }
+}
+impl From<NibBox> for NibVec {
+    fn from(boxed: NibBox) -> Self {
+        boxed.into_vec()
+    }
+}
+impl<'a> Borrow<dyn NibKey + 'a> for NibBox {
+    fn borrow(&self) -> &(dyn NibKey + 'a) {
+        self
+    }
+}
+impl slice::private::Sealed for NibBox {
+    #[inline(always)]
+    fn has_left_hi(&self) -> bool { true }
+    #[inline(always)]
+    fn has_right_lo(&self) -> bool { self.has_right_lo }
+    #[inline(always)]
+    fn iter(&self) -> stdslice::Iter<u4x2> { self.inner.iter() }
+}
+impl slice::private::SealedMut for NibBox {
+    #[inline(always)]
+    fn iter_mut(&mut self) -> stdslice::IterMut<u4x2> { self.inner.iter_mut() }
+}
+impl slice::NibSliceExt for NibBox {}
+impl slice::NibSliceMutExt for NibBox {}
+
+macro_rules! do_shared {
+    ($($(#[$attr:meta])* $t:ident ($ptr:ident);)*) => {
+        $(
+            $(#[$attr])*
+            #[derive(Clone)]
+            pub struct $t {
+                inner: $ptr<[u4x2]>,
+                has_right_lo: bool,
+            }
+            impl $t {
+                /// Intreprets this pointer as a slice.
+                pub fn as_slice(&self) -> NibSliceAligned {
+                    aligned(&self.inner, self.has_right_lo)
+                }
+
+                /// Whether two pointers point to the same allocation.
+                pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+                    $ptr::ptr_eq(&this.inner, &other.inner)
+                }
+            }
+            impl From<NibVec> for $t {
+                fn from(vec: NibVec) -> Self {
+                    let (inner, has_right_lo) = vec.into_parts();
+                    $t { inner: inner.into(), has_right_lo }
+                }
+            }
+            impl From<NibBox> for $t {
+                fn from(boxed: NibBox) -> Self {
+                    $t { inner: boxed.inner.into(), has_right_lo: boxed.has_right_lo }
+                }
+            }
+            impl<'a> From<NibSlice<'a>> for $t {
+                fn from(slice: NibSlice<'a>) -> Self {
+                    slice.to_vec().into()
+                }
+            }
+            impl slice::private::Sealed for $t {
+                #[inline(always)]
+                fn has_left_hi(&self) -> bool { true }
+                #[inline(always)]
+                fn has_right_lo(&self) -> bool { self.has_right_lo }
+                #[inline(always)]
+                fn iter(&self) -> stdslice::Iter<u4x2> { self.inner.iter() }
+            }
+            impl slice::NibSliceExt for $t {}
+        )*
+    }
+}
+
+do_shared! {
+    /// A reference-counted slice of nibbles.
+    NibRc (Rc);
+
+    /// An atomically reference-counted slice of nibbles, which can be shared between threads.
+    NibArc (Arc);
+}
+
+/// A clone-on-write slice of nibbles.
+///
+/// Aligned slices can be borrowed as-is, but unaligned slices have to be realigned into a vector.
+#[derive(Clone)]
+pub enum NibCow<'a> {
+    /// Borrowed, aligned data.
+    Borrowed(NibSliceAligned<'a>),
+
+    /// Owned data.
+    Owned(NibVec),
+}
+impl<'a> NibCow<'a> {
+    /// Whether the data is borrowed.
+    pub fn is_borrowed(&self) -> bool {
+        match *self {
+            NibCow::Borrowed(_) => true,
+            NibCow::Owned(_) => false,
+        }
+    }
+
+    /// Whether the data is owned.
+    pub fn is_owned(&self) -> bool {
+        !self.is_borrowed()
+    }
+
+    /// Intreprets this data as a slice.
+    pub fn as_slice(&self) -> NibSliceAligned {
+        match *self {
+            NibCow::Borrowed(s) => s,
+            NibCow::Owned(ref v) => v.as_slice(),
+        }
+    }
+
+    /// Gets mutable access to the data, copying it if it's borrowed.
+    pub fn to_mut(&mut self) -> &mut NibVec {
+        if let NibCow::Borrowed(s) = *self {
+            *self = NibCow::Owned(s.to_vec());
+        }
+        match *self {
+            NibCow::Borrowed(_) => unreachable!(),
+            NibCow::Owned(ref mut v) => v,
+        }
+    }
+
+    /// Extracts the owned data, copying it if it's borrowed.
+    pub fn into_owned(self) -> NibVec {
+        match self {
+            NibCow::Borrowed(s) => s.to_vec(),
+            NibCow::Owned(v) => v,
+        }
+    }
+}
+impl<'a> From<NibSlice<'a>> for NibCow<'a> {
+    fn from(slice: NibSlice<'a>) -> Self {
+        match slice.as_aligned() {
+            Some(s) => NibCow::Borrowed(s),
+            None => NibCow::Owned(slice.to_vec()),
+        }
+    }
+}
+impl<'a> From<NibSliceAligned<'a>> for NibCow<'a> {
+    fn from(slice: NibSliceAligned<'a>) -> Self {
+        NibCow::Borrowed(slice)
+    }
+}
+impl<'a> From<&'a NibVec> for NibCow<'a> {
+    fn from(vec: &'a NibVec) -> Self {
+        NibCow::Borrowed(vec.as_slice())
+    }
+}
+impl<'a> From<NibVec> for NibCow<'a> {
+    fn from(vec: NibVec) -> Self {
+        NibCow::Owned(vec)
+    }
+}
+impl<'a> slice::private::Sealed for NibCow<'a> {
+    #[inline(always)]
+    fn has_left_hi(&self) -> bool { true }
+    fn has_right_lo(&self) -> bool {
+        match *self {
+            NibCow::Borrowed(ref s) => s.has_right_lo(),
+            NibCow::Owned(ref v) => v.has_right_lo(),
+        }
+    }
+    fn iter(&self) -> stdslice::Iter<u4x2> {
+        match *self {
+            NibCow::Borrowed(ref s) => s.iter(),
+            NibCow::Owned(ref v) => v.iter(),
+        }
+    }
+}
+impl<'a> slice::NibSliceExt for NibCow<'a> {}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::collections::HashMap;
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+    use base::{u4, u4lo};
+    use super::*;
+
+    fn nibs<S: ?Sized + NibSliceExt>(slice: &S) -> Vec<u8> {
+        slice.nibbles().map(|nib| nib.to_lo()).collect()
+    }
+
+    #[test]
+    fn boxed() {
+        let vec = NibVec::from_str("12345").unwrap();
+        let boxed = NibBox::from(vec.clone());
+        assert_eq!(boxed, vec);
+        let rc = NibRc::from(boxed.clone());
+        assert!(NibRc::ptr_eq(&rc, &rc.clone()));
+        assert_eq!(rc, vec);
+        let arc = NibArc::from(vec.slice(1..));
+        assert_eq!(nibs(&arc), [2, 3, 4, 5]);
+        assert_eq!(boxed.into_vec(), vec);
+    }
+
+    #[test]
+    fn cow() {
+        let vec = NibVec::from_str("12345").unwrap();
+        let cow = NibCow::from(vec.slice(..4));
+        assert!(cow.is_borrowed());
+        assert_eq!(nibs(&cow), [1, 2, 3, 4]);
+        let mut cow = NibCow::from(vec.slice(1..));
+        assert!(cow.is_owned());
+        assert_eq!(nibs(&cow), [2, 3, 4, 5]);
+        cow.to_mut().push(u4lo::from_lo(6));
+        assert_eq!(nibs(&cow.into_owned()), [2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn borrow() {
+        let vec = NibVec::from_str("1234").unwrap();
+        let key = match vec.as_slice() {
+            NibSliceAligned::Even(s) => s,
+            NibSliceAligned::Odd(_) => unreachable!(),
+        };
+        let mut map = HashMap::new();
+        map.insert(key.to_owned(), 1);
+        assert_eq!(map.get(key), Some(&1));
+        let cow: Cow<NibSliceFull> = Cow::Borrowed(key);
+        assert_eq!(nibs(&*cow.into_owned()), [1, 2, 3, 4]);
+    }
+
+    fn hash<T: ?Sized + Hash>(val: &T) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        val.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn key() {
+        let vec = NibVec::from_str("012345").unwrap();
+        let (odd, even) = (vec.slice(1..4), vec.slice(1..5));
+        assert_eq!(odd, NibVec::from_str("123").unwrap());
+        assert_eq!(hash(&odd), hash(&NibVec::from_str("123").unwrap()));
+        assert_eq!(hash(&even), hash(&NibVec::from_str("1234").unwrap()));
+        assert_ne!(hash(&NibVec::from_str("1").unwrap()), hash(&NibVec::from_str("10").unwrap()));
+
+        let mut map = HashMap::new();
+        map.insert(odd.to_vec(), 3);
+        map.insert(even.to_vec(), 4);
+        map.insert(NibVec::new(), 0);
+        let boxed = NibBox::from(NibVec::from_str("1230").unwrap());
+        assert_eq!(map.get(&odd as &dyn NibKey), Some(&3));
+        assert_eq!(map.get(&vec.slice(2..4) as &dyn NibKey), None);
+        assert_eq!(map.get(&NibVec::from_str("1234").unwrap().as_slice() as &dyn NibKey), Some(&4));
+        assert_eq!(map.get(&boxed.as_slice() as &dyn NibKey), None);
+        assert_eq!(map.get(Borrow::<dyn NibKey>::borrow(&NibVec::new())), Some(&0));
+
+        let mut boxes = HashMap::new();
+        boxes.insert(boxed.clone(), ());
+        let key: &dyn NibKey = boxed.borrow();
+        assert_eq!(nibs(&key.as_key_slice()), [1, 2, 3, 0]);
+        assert!(boxes.contains_key(key));
+        assert!(boxes.contains_key(&NibVec::from_str("01230").unwrap().slice(1..) as &dyn NibKey));
+        assert!(!boxes.contains_key(&vec.slice(1..4) as &dyn NibKey));
+    }
+}
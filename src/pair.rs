@@ -32,7 +32,7 @@ impl u4x2 {
 
     /// Creates a pair from an already-combined byte.
     #[inline(always)]
-    pub fn from_byte(byte: u8) -> u4x2 {
+    pub const fn from_byte(byte: u8) -> u4x2 {
         u4x2 { byte }
     }
 
@@ -0,0 +1,494 @@
+//! Types for editing large buffers of nibbles.
+use std::ops::RangeBounds;
+use base::u4;
+use iter::Nibbles;
+use slice::{NibSlice, NibSliceExt};
+use vec::NibVec;
+use common::{nib_range, XorShift};
+
+/// Which buffer a piece of a rope comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Source {
+    Original,
+    Added,
+}
+
+/// A run of nibbles from one of the buffers of a rope.
+#[derive(Clone, Copy, Debug)]
+struct Piece {
+    source: Source,
+    start: usize,
+    len: usize,
+}
+
+/// A piece in the tree of a rope.
+///
+/// The tree is a treap: nodes are ordered by position and form a heap by priority, which keeps it
+/// balanced in expectation.
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    piece: Piece,
+    priority: u64,
+    /// Number of nibbles in this subtree.
+    len: usize,
+    left: Option<usize>,
+    right: Option<usize>,
+}
+
+/// A piece table of nibbles.
+///
+/// The rope borrows an original slice and keeps every inserted nibble in a separate, append-only
+/// buffer. Edits only touch the pieces, so inserting or removing nibbles never moves the existing
+/// data, however large it is. The pieces are kept in a balanced tree by position, so finding,
+/// splitting, inserting and removing them takes logarithmic time.
+#[derive(Clone, Debug)]
+pub struct NibRope<'a> {
+    original: NibSlice<'a>,
+    added: NibVec,
+    nodes: Vec<Node>,
+    free: Vec<usize>,
+    root: Option<usize>,
+    priorities: XorShift,
+}
+impl<'a> NibRope<'a> {
+    /// Creates a rope which initially holds the given slice.
+    pub fn new(original: NibSlice<'a>) -> Self {
+        let len = original.len();
+        let mut rope = NibRope {
+            original,
+            added: NibVec::new(),
+            nodes: Vec::new(),
+            free: Vec::new(),
+            root: None,
+            priorities: XorShift(0x9e37_79b9_7f4a_7c15),
+        };
+        if len != 0 {
+            rope.root = Some(rope.alloc(Piece { source: Source::Original, start: 0, len }));
+        }
+        rope
+    }
+
+    /// Number of nibbles in the rope.
+    pub fn len(&self) -> usize {
+        self.tree_len(self.root)
+    }
+
+    /// Whether the rope is empty.
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Views one piece as a slice.
+    fn piece(&self, piece: &Piece) -> NibSlice {
+        match piece.source {
+            Source::Original => self.original.sub(piece.start, piece.start + piece.len),
+            Source::Added => self.added.slice(piece.start..piece.start + piece.len),
+        }
+    }
+
+    /// Adds a lone node for the given piece.
+    fn alloc(&mut self, piece: Piece) -> usize {
+        let node = Node { piece, priority: self.priorities.next(), len: piece.len, left: None, right: None };
+        match self.free.pop() {
+            Some(n) => {
+                self.nodes[n] = node;
+                n
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    /// Number of nibbles in a subtree.
+    fn tree_len(&self, tree: Option<usize>) -> usize {
+        tree.map_or(0, |n| self.nodes[n].len)
+    }
+
+    /// Recomputes the length of a node after its children change.
+    fn update(&mut self, n: usize) {
+        let Node { piece, left, right, .. } = self.nodes[n];
+        self.nodes[n].len = piece.len + self.tree_len(left) + self.tree_len(right);
+    }
+
+    /// Joins two trees, with all of `a` before all of `b`.
+    fn merge(&mut self, a: Option<usize>, b: Option<usize>) -> Option<usize> {
+        match (a, b) {
+            (None, tree) | (tree, None) => tree,
+            (Some(a), Some(b)) => if self.nodes[a].priority > self.nodes[b].priority {
+                let right = self.nodes[a].right;
+                self.nodes[a].right = self.merge(right, Some(b));
+                self.update(a);
+                Some(a)
+            } else {
+                let left = self.nodes[b].left;
+                self.nodes[b].left = self.merge(Some(a), left);
+                self.update(b);
+                Some(b)
+            },
+        }
+    }
+
+    /// Splits a tree into its first `idx` nibbles and the rest, splitting a piece if needed.
+    fn split(&mut self, tree: Option<usize>, idx: usize) -> (Option<usize>, Option<usize>) {
+        let n = match tree {
+            Some(n) => n,
+            None => return (None, None),
+        };
+        let Node { piece, left, right, .. } = self.nodes[n];
+        let left_len = self.tree_len(left);
+        if idx <= left_len {
+            let (l, r) = self.split(left, idx);
+            self.nodes[n].left = r;
+            self.update(n);
+            (l, Some(n))
+        } else if idx >= left_len + piece.len {
+            let (l, r) = self.split(right, idx - left_len - piece.len);
+            self.nodes[n].right = l;
+            self.update(n);
+            (Some(n), r)
+        } else {
+            let mid = idx - left_len;
+            let rest = self.alloc(Piece { start: piece.start + mid, len: piece.len - mid, ..piece });
+            self.nodes[n].piece.len = mid;
+            self.nodes[n].right = None;
+            self.update(n);
+            (Some(n), self.merge(Some(rest), right))
+        }
+    }
+
+    /// Extends the last piece of a tree by the given added nibbles, if they directly follow it.
+    fn extend_last(&mut self, tree: Option<usize>, start: usize, len: usize) -> bool {
+        let n = match tree {
+            Some(n) => n,
+            None => return false,
+        };
+        let extended = match self.nodes[n].right {
+            Some(right) => self.extend_last(Some(right), start, len),
+            None => {
+                let piece = &mut self.nodes[n].piece;
+                let extends = piece.source == Source::Added && piece.start + piece.len == start;
+                if extends {
+                    piece.len += len;
+                }
+                extends
+            }
+        };
+        if extended {
+            self.nodes[n].len += len;
+        }
+        extended
+    }
+
+    /// Gets the nibble at the given index.
+    pub fn get<T: u4>(&self, mut idx: usize) -> Option<T> {
+        let mut tree = self.root;
+        while let Some(n) = tree {
+            let Node { ref piece, left, right, .. } = self.nodes[n];
+            let left_len = self.tree_len(left);
+            if idx < left_len {
+                tree = left;
+            } else if idx < left_len + piece.len {
+                return self.piece(piece).get(idx - left_len).map(|nib| T::from_lo(nib.to_lo()));
+            } else {
+                idx -= left_len + piece.len;
+                tree = right;
+            }
+        }
+        None
+    }
+
+    /// Inserts a nibble at the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    pub fn insert<T: u4>(&mut self, index: usize, nib: T) {
+        let mut nibs = NibVec::new();
+        nibs.push(nib);
+        self.insert_nibs(index, &nibs);
+    }
+
+    /// Inserts all the nibbles from a slice at the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    pub fn insert_nibs<S: ?Sized + NibSliceExt>(&mut self, index: usize, nibs: &S) {
+        let self_len = self.len();
+        assert!(index <= self_len, "insertion index {} out of range for rope of length {}", index, self_len);
+        let len = nibs.len();
+        if len == 0 {
+            return;
+        }
+        let start = self.added.len();
+        self.added.extend_from_nibs(nibs);
+
+        // typing at the end of the last insertion just extends it
+        let (before, after) = self.split(self.root, index);
+        let before = if self.extend_last(before, start, len) {
+            before
+        } else {
+            let piece = self.alloc(Piece { source: Source::Added, start, len });
+            self.merge(before, Some(piece))
+        };
+        self.root = self.merge(before, after);
+    }
+
+    /// Removes a nibble from the rope at the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= len`.
+    pub fn remove<T: u4>(&mut self, index: usize) -> T {
+        let len = self.len();
+        assert!(index < len, "removal index {} out of range for rope of length {}", index, len);
+        let nib = self.get(index).unwrap_or_else(|| unreachable!());
+        self.remove_range(index..index + 1);
+        nib
+    }
+
+    /// Removes the given range of nibbles from the rope.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn remove_range<R: RangeBounds<usize>>(&mut self, range: R) {
+        let (start, end) = nib_range(range, self.len());
+        if start == end {
+            return;
+        }
+        let (before, rest) = self.split(self.root, start);
+        let (removed, after) = self.split(rest, end - start);
+        let mut stack: Vec<usize> = removed.into_iter().collect();
+        while let Some(n) = stack.pop() {
+            stack.extend(self.nodes[n].left);
+            stack.extend(self.nodes[n].right);
+            self.free.push(n);
+        }
+        self.root = self.merge(before, after);
+    }
+
+    /// Iterator over the pieces of the rope, in order.
+    pub fn pieces(&self) -> Pieces {
+        let mut pieces = Pieces { rope: self, front: Vec::new(), back: Vec::new(), len: self.nodes.len() - self.free.len() };
+        pieces.push_left(self.root);
+        pieces.push_right(self.root);
+        pieces
+    }
+
+    /// Iterator over the nibbles in the rope.
+    pub fn nibbles(&self) -> RopeNibbles {
+        RopeNibbles { pieces: self.pieces(), front: None, back: None, len: self.len() }
+    }
+
+    /// Flattens the rope into a new, aligned `NibVec`.
+    pub fn to_vec(&self) -> NibVec {
+        let mut vec = NibVec::with_capacity(self.len());
+        for piece in self.pieces() {
+            vec.extend_from_nibs(&piece);
+        }
+        vec
+    }
+}
+impl<'a> From<NibSlice<'a>> for NibRope<'a> {
+    fn from(slice: NibSlice<'a>) -> Self {
+        NibRope::new(slice)
+    }
+}
+
+/// Iterator over the pieces of a `NibRope`.
+#[derive(Clone, Debug)]
+pub struct Pieces<'r> {
+    rope: &'r NibRope<'r>,
+    front: Vec<usize>,
+    back: Vec<usize>,
+    len: usize,
+}
+impl<'r> Pieces<'r> {
+    /// Pushes a subtree and its leftmost descendants onto the front stack.
+    fn push_left(&mut self, mut tree: Option<usize>) {
+        while let Some(n) = tree {
+            self.front.push(n);
+            tree = self.rope.nodes[n].left;
+        }
+    }
+
+    /// Pushes a subtree and its rightmost descendants onto the back stack.
+    fn push_right(&mut self, mut tree: Option<usize>) {
+        while let Some(n) = tree {
+            self.back.push(n);
+            tree = self.rope.nodes[n].right;
+        }
+    }
+}
+impl<'r> Iterator for Pieces<'r> {
+    type Item = NibSlice<'r>;
+    fn next(&mut self) -> Option<NibSlice<'r>> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        let rope = self.rope;
+        let n = self.front.pop().unwrap_or_else(|| unreachable!());
+        self.push_left(rope.nodes[n].right);
+        Some(rope.piece(&rope.nodes[n].piece))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+impl<'r> DoubleEndedIterator for Pieces<'r> {
+    fn next_back(&mut self) -> Option<NibSlice<'r>> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        let rope = self.rope;
+        let n = self.back.pop().unwrap_or_else(|| unreachable!());
+        self.push_right(rope.nodes[n].left);
+        Some(rope.piece(&rope.nodes[n].piece))
+    }
+}
+impl<'r> ExactSizeIterator for Pieces<'r> {}
+
+/// Iterator over the nibbles in a `NibRope`.
+#[derive(Debug)]
+pub struct RopeNibbles<'r> {
+    pieces: Pieces<'r>,
+    front: Option<Nibbles<'r>>,
+    back: Option<Nibbles<'r>>,
+    len: usize,
+}
+impl<'r> Iterator for RopeNibbles<'r> {
+    type Item = &'r u4;
+    fn next(&mut self) -> Option<&'r u4> {
+        loop {
+            if let Some(ref mut inner) = self.front {
+                if let Some(x) = inner.next() {
+                    self.len -= 1;
+                    return Some(x);
+                }
+            }
+            match self.pieces.next() {
+                None => {
+                    let x = self.back.as_mut().and_then(|it| it.next());
+                    if x.is_some() {
+                        self.len -= 1;
+                    }
+                    return x;
+                }
+                next => self.front = next.map(NibSlice::into_nibbles),
+            }
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+impl<'r> DoubleEndedIterator for RopeNibbles<'r> {
+    fn next_back(&mut self) -> Option<&'r u4> {
+        loop {
+            if let Some(ref mut inner) = self.back {
+                if let Some(x) = inner.next_back() {
+                    self.len -= 1;
+                    return Some(x);
+                }
+            }
+            match self.pieces.next_back() {
+                None => {
+                    let x = self.front.as_mut().and_then(|it| it.next_back());
+                    if x.is_some() {
+                        self.len -= 1;
+                    }
+                    return x;
+                }
+                next => self.back = next.map(NibSlice::into_nibbles),
+            }
+        }
+    }
+}
+impl<'r> ExactSizeIterator for RopeNibbles<'r> {}
+
+#[cfg(test)]
+mod tests {
+    use base::u4lo;
+    use super::*;
+    use common::XorShift;
+
+    fn nibs(rope: &NibRope) -> Vec<u8> {
+        rope.nibbles().map(|nib| nib.to_lo()).collect()
+    }
+
+    #[test]
+    fn edit() {
+        let buf = NibVec::from_str("0123456789").unwrap();
+        let mut rope = NibRope::new(buf.slice(..));
+        rope.insert(1, u4lo::from_lo(0xa));
+        rope.insert(2, u4lo::from_lo(0xb));
+        assert_eq!(rope.pieces().len(), 3);
+        rope.remove_range(4..7);
+        assert_eq!(nibs(&rope), [0, 0xa, 0xb, 1, 5, 6, 7, 8, 9]);
+        assert_eq!(rope.remove::<u4lo>(0).to_lo(), 0);
+        assert_eq!(rope.get::<u4lo>(0).map(|nib| nib.to_lo()), Some(0xa));
+        assert_eq!(rope.to_vec(), NibVec::from_str("ab156789").unwrap());
+        assert_eq!(rope.nibbles().rev().map(|nib| nib.to_lo()).collect::<Vec<_>>(), [9, 8, 7, 6, 5, 1, 0xb, 0xa]);
+    }
+
+    fn depth(rope: &NibRope, tree: Option<usize>) -> usize {
+        tree.map_or(0, |n| 1 + depth(rope, rope.nodes[n].left).max(depth(rope, rope.nodes[n].right)))
+    }
+
+    #[test]
+    fn balanced() {
+        let buf = NibVec::from_str("0123456789").unwrap();
+        let mut rope = NibRope::new(buf.slice(..));
+        for i in 0..1000 {
+            rope.insert(0, u4lo::from_lo(i as u8 & 0xF));
+        }
+        assert_eq!(rope.pieces().len(), 1001);
+        assert!(depth(&rope, rope.root) < 40);
+        assert_eq!(rope.get::<u4lo>(999).map(|nib| nib.to_lo()), Some(0));
+        assert_eq!(rope.get::<u4lo>(1003).map(|nib| nib.to_lo()), Some(3));
+
+        rope.remove_range(..990);
+        assert_eq!(rope.pieces().len(), 11);
+        assert_eq!(rope.to_vec(), NibVec::from_str("98765432100123456789").unwrap());
+    }
+
+    #[test]
+    fn model() {
+        let mut rng = XorShift(0x8f1b_bcdc_bfa5_3e0b);
+        let buf: NibVec = (0..101).map(|_| u4lo::from_lo(rng.nib())).collect();
+        let mut rope = NibRope::new(buf.slice(1..));
+        let mut model: Vec<u8> = buf.nibbles().skip(1).map(|nib| nib.to_lo()).collect();
+        for _ in 0..300 {
+            let len = model.len();
+            match rng.below(3) {
+                0 => {
+                    let (idx, nib) = (rng.below(len + 1), rng.nib());
+                    rope.insert(idx, u4lo::from_lo(nib));
+                    model.insert(idx, nib);
+                }
+                1 => {
+                    let other: Vec<u8> = (0..rng.below(5)).map(|_| rng.nib()).collect();
+                    let idx = rng.below(len + 1);
+                    rope.insert_nibs(idx, &other.iter().map(|&nib| u4lo::from_lo(nib)).collect::<NibVec>());
+                    for (i, &nib) in other.iter().enumerate() {
+                        model.insert(idx + i, nib);
+                    }
+                }
+                _ => {
+                    let (start, end) = rng.range(len);
+                    rope.remove_range(start..end);
+                    model.drain(start..end);
+                }
+            }
+            assert_eq!(rope.len(), model.len());
+            assert_eq!(nibs(&rope), model);
+            assert!(rope.nibbles().rev().map(|nib| nib.to_lo()).eq(model.iter().rev().cloned()));
+        }
+        assert_eq!(rope.to_vec().nibbles().map(|nib| nib.to_lo()).collect::<Vec<_>>(), model);
+    }
+}
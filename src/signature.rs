@@ -0,0 +1,353 @@
+//! Byte signatures with nibble-level wildcards.
+//!
+//! Signatures are written in the usual hex-pattern style, e.g. `48 8B ?? ?5 E8`. Every
+//! whitespace-separated token is one byte, written as two characters: a hex digit is a fixed
+//! nibble and a `?` is a wildcard nibble. A lone `?` stands for a whole wildcard byte.
+use base::{u4, u4lo, ParseNibbleError};
+use slice::NibSliceExt;
+
+/// A pattern of nibbles, each of which is either fixed or a wildcard.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Signature {
+    value: Vec<u8>,
+    mask: Vec<u8>,
+    len: usize,
+}
+impl Signature {
+    /// Parses a signature from an ASCII string.
+    ///
+    /// Fails with `ParseNibbleError::BadFormat` if any token isn't exactly one byte.
+    pub fn from_ascii(s: &[u8]) -> Result<Self, ParseNibbleError> {
+        let mut sig = Signature { value: Vec::new(), mask: Vec::new(), len: 0 };
+        for token in s.split(|b| b.is_ascii_whitespace()).filter(|t| !t.is_empty()) {
+            let token = match token {
+                b"?" => b"??",
+                _ if token.len() == 2 => token,
+                _ => return Err(ParseNibbleError::BadFormat),
+            };
+            for &b in token {
+                if b == b'?' {
+                    sig.push(None);
+                } else {
+                    sig.push(Some(u4lo::from_ascii_digit(b).ok_or(ParseNibbleError::BadFormat)?));
+                }
+            }
+        }
+        if sig.len == 0 {
+            Err(ParseNibbleError::Empty)
+        } else {
+            Ok(sig)
+        }
+    }
+
+    /// Parses a signature from a string.
+    pub fn from_str(s: &str) -> Result<Self, ParseNibbleError> {
+        Self::from_ascii(s.as_bytes())
+    }
+
+    fn push(&mut self, nib: Option<u4lo>) {
+        let (value, mask) = match nib {
+            Some(nib) => (nib.to_lo(), 0xF),
+            None => (0, 0),
+        };
+        if self.len & 1 == 0 {
+            self.value.push(value << 4);
+            self.mask.push(mask << 4);
+        } else {
+            let i = self.value.len() - 1;
+            self.value[i] |= value;
+            self.mask[i] |= mask;
+        }
+        self.len += 1;
+    }
+
+    /// Number of nibbles in the signature.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the signature is empty.
+    ///
+    /// Parsed signatures are never empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Gets the nibble at the given index, or `None` if it's a wildcard.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn get(&self, idx: usize) -> Option<u4lo> {
+        assert!(idx < self.len, "index {} out of range for signature of length {}", idx, self.len);
+        let shift = if idx & 1 == 0 { 4 } else { 0 };
+        if (self.mask[idx >> 1] >> shift) & 0xF == 0 {
+            None
+        } else {
+            Some(u4lo::from_lo((self.value[idx >> 1] >> shift) & 0xF))
+        }
+    }
+
+    /// Checks whether the signature matches the bytes starting at the given byte offset.
+    pub fn matches_bytes_at(&self, bytes: &[u8], offset: usize) -> bool {
+        match bytes.get(offset..offset + self.value.len()) {
+            Some(window) => window.iter()
+                .zip(self.value.iter().zip(self.mask.iter()))
+                .all(|(&b, (&value, &mask))| b & mask == value),
+            None => false,
+        }
+    }
+
+    /// Checks whether the signature matches the nibbles starting at the given nibble index.
+    pub fn matches_at<S: ?Sized + NibSliceExt>(&self, nibs: &S, idx: usize) -> bool {
+        if idx + self.len > nibs.len() {
+            return false;
+        }
+        (0..self.len).all(|i| match self.get(i) {
+            Some(nib) => unsafe { nibs.get_unchecked(idx + i) }.to_lo() == nib.to_lo(),
+            None => true,
+        })
+    }
+
+    /// Iterator over every byte offset in `bytes` where the signature matches.
+    pub fn scan_bytes<'a, 'b>(&'a self, bytes: &'b [u8]) -> ByteMatches<'a, 'b> {
+        ByteMatches { sig: self, bytes, offset: 0 }
+    }
+
+    /// Iterator over every nibble index in `nibs` where the signature matches.
+    pub fn scan<'a, 'b, S: ?Sized + NibSliceExt>(&'a self, nibs: &'b S) -> Matches<'a, 'b, S> {
+        Matches { sig: self, nibs, idx: 0 }
+    }
+}
+
+/// Iterator over the byte offsets where a signature matches.
+#[derive(Clone, Debug)]
+pub struct ByteMatches<'a, 'b> {
+    sig: &'a Signature,
+    bytes: &'b [u8],
+    offset: usize,
+}
+impl<'a, 'b> Iterator for ByteMatches<'a, 'b> {
+    type Item = usize;
+    fn next(&mut self) -> Option<usize> {
+        while self.offset < self.bytes.len() {
+            self.offset += 1;
+            if self.sig.matches_bytes_at(self.bytes, self.offset - 1) {
+                return Some(self.offset - 1);
+            }
+        }
+        None
+    }
+}
+
+/// Iterator over the nibble indices where a signature matches.
+#[derive(Debug)]
+pub struct Matches<'a, 'b, S: 'b + ?Sized> {
+    sig: &'a Signature,
+    nibs: &'b S,
+    idx: usize,
+}
+impl<'a, 'b, S: 'b + ?Sized + NibSliceExt> Iterator for Matches<'a, 'b, S> {
+    type Item = usize;
+    fn next(&mut self) -> Option<usize> {
+        while self.idx < self.nibs.len() {
+            self.idx += 1;
+            if self.sig.matches_at(self.nibs, self.idx - 1) {
+                return Some(self.idx - 1);
+            }
+        }
+        None
+    }
+}
+
+/// A set of signatures which can be scanned for at once.
+///
+/// Signatures are indexed by the bytes their first byte can match, and by the nibbles their first
+/// nibble can match, so scanning only tries the signatures which can start at each position.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SignatureSet {
+    sigs: Vec<Signature>,
+    by_first: Vec<Vec<usize>>,
+    by_first_nib: Vec<Vec<usize>>,
+}
+impl SignatureSet {
+    /// Creates an empty set.
+    pub fn new() -> Self {
+        SignatureSet { sigs: Vec::new(), by_first: Vec::new(), by_first_nib: Vec::new() }
+    }
+
+    /// Adds a signature to the set, returning its index.
+    pub fn push(&mut self, sig: Signature) -> usize {
+        let idx = self.sigs.len();
+        if self.by_first.is_empty() {
+            self.by_first.resize(256, Vec::new());
+            self.by_first_nib.resize(16, Vec::new());
+        }
+        let (value, mask) = (sig.value[0], sig.mask[0]);
+        for (byte, candidates) in self.by_first.iter_mut().enumerate() {
+            if byte as u8 & mask == value {
+                candidates.push(idx);
+            }
+        }
+        for (nib, candidates) in self.by_first_nib.iter_mut().enumerate() {
+            if (nib as u8) << 4 & mask == value & 0xF0 {
+                candidates.push(idx);
+            }
+        }
+        self.sigs.push(sig);
+        idx
+    }
+
+    /// The signatures in the set.
+    pub fn signatures(&self) -> &[Signature] {
+        &self.sigs
+    }
+
+    /// Iterator over every match in `bytes`, as pairs of the byte offset and the index of the
+    /// signature which matched.
+    ///
+    /// Matches are ordered by offset, then by signature index.
+    pub fn scan_bytes<'a, 'b>(&'a self, bytes: &'b [u8]) -> SetByteMatches<'a, 'b> {
+        SetByteMatches { set: self, bytes, offset: 0, candidate: 0 }
+    }
+
+    /// Iterator over every match in `nibs`, as pairs of the nibble index and the index of the
+    /// signature which matched.
+    ///
+    /// Matches are ordered by nibble index, then by signature index.
+    pub fn scan<'a, 'b, S: ?Sized + NibSliceExt>(&'a self, nibs: &'b S) -> SetMatches<'a, 'b, S> {
+        SetMatches { set: self, nibs, idx: 0, candidate: 0 }
+    }
+}
+impl ::std::iter::FromIterator<Signature> for SignatureSet {
+    fn from_iter<I: IntoIterator<Item = Signature>>(iter: I) -> Self {
+        let mut set = SignatureSet::new();
+        for sig in iter {
+            set.push(sig);
+        }
+        set
+    }
+}
+
+/// Iterator over the matches of a set of signatures.
+#[derive(Clone, Debug)]
+pub struct SetByteMatches<'a, 'b> {
+    set: &'a SignatureSet,
+    bytes: &'b [u8],
+    offset: usize,
+    candidate: usize,
+}
+impl<'a, 'b> Iterator for SetByteMatches<'a, 'b> {
+    type Item = (usize, usize);
+    fn next(&mut self) -> Option<(usize, usize)> {
+        if self.set.sigs.is_empty() {
+            return None;
+        }
+        while self.offset < self.bytes.len() {
+            let candidates = &self.set.by_first[self.bytes[self.offset] as usize];
+            while self.candidate < candidates.len() {
+                let sig = candidates[self.candidate];
+                self.candidate += 1;
+                if self.set.sigs[sig].matches_bytes_at(self.bytes, self.offset) {
+                    return Some((self.offset, sig));
+                }
+            }
+            self.candidate = 0;
+            self.offset += 1;
+        }
+        None
+    }
+}
+
+/// Iterator over the nibble indices where the signatures in a set match.
+#[derive(Debug)]
+pub struct SetMatches<'a, 'b, S: 'b + ?Sized> {
+    set: &'a SignatureSet,
+    nibs: &'b S,
+    idx: usize,
+    candidate: usize,
+}
+impl<'a, 'b, S: 'b + ?Sized + NibSliceExt> Iterator for SetMatches<'a, 'b, S> {
+    type Item = (usize, usize);
+    fn next(&mut self) -> Option<(usize, usize)> {
+        if self.set.sigs.is_empty() {
+            return None;
+        }
+        while self.idx < self.nibs.len() {
+            let nib = unsafe { self.nibs.get_unchecked(self.idx) }.to_lo();
+            let candidates = &self.set.by_first_nib[nib as usize];
+            while self.candidate < candidates.len() {
+                let sig = candidates[self.candidate];
+                self.candidate += 1;
+                if self.set.sigs[sig].matches_at(self.nibs, self.idx) {
+                    return Some((self.idx, sig));
+                }
+            }
+            self.candidate = 0;
+            self.idx += 1;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vec::NibVec;
+
+    #[test]
+    fn parse() {
+        let sig = Signature::from_str("48 8B ?? ?5 E8").unwrap();
+        assert_eq!(sig.len(), 10);
+        assert_eq!(sig.get(0).map(|n| n.to_lo()), Some(4));
+        assert!(sig.get(4).is_none());
+        assert!(sig.get(6).is_none());
+        assert_eq!(sig.get(7).map(|n| n.to_lo()), Some(5));
+        assert_eq!(Signature::from_str("48 ? E8").unwrap(), Signature::from_str("48\t??  E8").unwrap());
+        assert_eq!(sig.to_string(), "48 8B ?? ?5 E8");
+        assert_eq!(Signature::from_str("  "), Err(ParseNibbleError::Empty));
+        assert_eq!(Signature::from_str("4G"), Err(ParseNibbleError::BadFormat));
+
+        // every token is exactly one byte
+        assert_eq!(Signature::from_str("48 8B ?? ?5 E"), Err(ParseNibbleError::BadFormat));
+        assert_eq!(Signature::from_str("4 8B"), Err(ParseNibbleError::BadFormat));
+        assert_eq!(Signature::from_str("4 8"), Err(ParseNibbleError::BadFormat));
+        assert_eq!(Signature::from_str("488B"), Err(ParseNibbleError::BadFormat));
+        assert_eq!(Signature::from_str("48 ???"), Err(ParseNibbleError::BadFormat));
+    }
+
+    #[test]
+    fn scan() {
+        let bytes = [0x48, 0x8B, 0x05, 0x15, 0xE8, 0x48, 0x8B, 0xFF, 0x25, 0xE8, 0x48, 0x8B];
+        let sig = Signature::from_str("48 8B ?? ?5 E8").unwrap();
+        assert_eq!(sig.scan_bytes(&bytes).collect::<Vec<_>>(), [0, 5]);
+
+        let set: SignatureSet = vec![sig, Signature::from_str("?5 E?").unwrap()]
+            .into_iter()
+            .collect();
+        let matches: Vec<_> = set.scan_bytes(&bytes).collect();
+        assert_eq!(matches, [(0, 0), (3, 1), (5, 0), (8, 1)]);
+
+        let mut set = SignatureSet::new();
+        set.push(Signature::from_str("8B").unwrap());
+        set.push(Signature::from_str("?? 8B").unwrap());
+        set.push(Signature::from_str("E8 48").unwrap());
+        let matches: Vec<_> = set.scan_bytes(&bytes).collect();
+        assert_eq!(matches, [(0, 1), (1, 0), (4, 2), (5, 1), (6, 0), (9, 2), (10, 1), (11, 0)]);
+        assert_eq!(SignatureSet::new().scan_bytes(&bytes).next(), None);
+
+        let nibs = NibVec::from_byte_vec(bytes.to_vec());
+        let sig = Signature::from_str("B?").unwrap();
+        assert_eq!(sig.scan(&nibs).collect::<Vec<_>>(), [3, 13]);
+
+        set.push(Signature::from_str("?5 E?").unwrap());
+        let slice = nibs.slice(1..);
+        let expected: Vec<_> = (0..slice.len())
+            .flat_map(|idx| (0..4).map(move |sig| (idx, sig)))
+            .filter(|&(idx, sig)| set.signatures()[sig].matches_at(&slice, idx))
+            .collect();
+        assert_eq!(set.scan(&slice).collect::<Vec<_>>(), expected);
+        assert_eq!(expected[..4], [(1, 0), (5, 3), (7, 2), (9, 1)]);
+        assert_eq!(SignatureSet::new().scan(&nibs).next(), None);
+    }
+}
@@ -1,9 +1,18 @@
 //! Traits for dealing with slices of nibbles.
+use core::cell::Cell;
+use core::marker::PhantomData;
+use core::cmp::Ordering;
 use core::slice;
+use core::ops::RangeBounds;
 use base::{u4hi, u4lo, u4};
-use iter::{NibblePairs, NibblePairsMut, Nibbles, NibblesMut};
+use iter::{NibblePairs, NibblePairsMut, Nibbles, NibblesMut, NibbleCells};
+use iter::{Chunks, ChunksExact, RChunks, Windows, ChunksMut, ChunksExactMut, RChunksMut};
+use iter::{MatchIndices, Split, RealignedBytes};
 use pair::{U4HiCell, U4LoCell, U4Cell, u4x2};
-use common::{get_nib_ref, get_nib_mut};
+use order::{HiFirst, NibOrder};
+use common::{as_pairs, as_pairs_mut, copy_nibs, fill_nibs, find_nib, rfind_nib, is_hi, nib_range, pair_range, reverse_nibs};
+#[cfg(feature = "std")]
+use vec::NibVec;
 
 pub(crate) mod private {
     use super::{slice, u4x2};
@@ -62,38 +71,276 @@ pub trait NibSliceExt: private::Sealed {
         }
     }
 
-    /// Gets a nibble at the given index.
-    fn get(&self, idx: usize) -> &u4 {
-        get_nib_ref(self.iter().as_slice(), idx)
+    /// Gets a nibble at the given index, if it's in bounds.
+    fn get(&self, idx: usize) -> Option<&u4> {
+        if idx < self.len() {
+            Some(unsafe { self.get_unchecked(idx) })
+        } else {
+            None
+        }
+    }
+
+    /// Gets a nibble at the given index without bounds checking.
+    ///
+    /// Calling this with an index that's out of bounds is undefined behaviour.
+    unsafe fn get_unchecked(&self, idx: usize) -> &u4 {
+        let idx = idx + !self.has_left_hi() as usize;
+        let pair = self.iter().as_slice().get_unchecked(idx >> 1);
+        if idx & 1 == 0 {
+            pair.hi()
+        } else {
+            pair.lo()
+        }
+    }
+
+    /// Gets the first nibble of the slice, if it's not empty.
+    fn first(&self) -> Option<&u4> {
+        self.get(0)
+    }
+
+    /// Gets the last nibble of the slice, if it's not empty.
+    fn last(&self) -> Option<&u4> {
+        self.len().checked_sub(1).and_then(|idx| self.get(idx))
     }
 
     /// Gets the length of the slice.
     fn len(&self) -> usize {
-        let hi = self.has_left_hi() as usize;
-        let lo = self.has_right_lo() as usize;
-        self.iter().as_slice().len().saturating_sub(hi + lo)
+        let hi = !self.has_left_hi() as usize;
+        let lo = !self.has_right_lo() as usize;
+        (self.iter().as_slice().len() << 1).saturating_sub(hi + lo)
     }
 
     /// Checks if the slice is empty.
     fn is_empty(&self) -> bool {
-        self.iter().as_slice().is_empty()
+        self.len() == 0
     }
 
-    /// Converts this slice into a `NibSlice`.
-    fn into_generic(&self) -> NibSlice {
-        if self.has_left_hi() {
-            if self.has_right_lo() {
-                NibSliceFull::from_slice(self.iter().as_slice()).into()
-            } else {
-                NibSliceNoR::from_slice(self.iter().as_slice()).into()
+    /// Gets the nibbles in the given range as a subslice.
+    ///
+    /// The kind of slice returned depends on whether the range starts and ends on a pair
+    /// boundary.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds or its start is greater than its end.
+    fn slice<R: RangeBounds<usize>>(&self, range: R) -> NibSlice {
+        let (start, end) = nib_range(range, self.len());
+        self.into_generic().sub(start, end)
+    }
+
+    /// Divides the slice into two at the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mid > len`.
+    fn split_at(&self, mid: usize) -> (NibSlice, NibSlice) {
+        let len = self.len();
+        assert!(mid <= len, "split index {} out of range for slice of length {}", mid, len);
+        let slice = self.into_generic();
+        (slice.sub(0, mid), slice.sub(mid, len))
+    }
+
+    /// Splits off the first nibble of the slice, if it's not empty.
+    fn split_first(&self) -> Option<(&u4, NibSlice)> {
+        let len = self.len();
+        self.first().map(|first| (first, self.into_generic().sub(1, len)))
+    }
+
+    /// Splits off the last nibble of the slice, if it's not empty.
+    fn split_last(&self) -> Option<(&u4, NibSlice)> {
+        let len = self.len();
+        self.last().map(|last| (last, self.into_generic().sub(0, len - 1)))
+    }
+
+    /// Iterator over chunks of `size` nibbles, starting at the beginning of the slice.
+    ///
+    /// The last chunk will be shorter if `size` does not divide the length of the slice.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero.
+    fn chunks(&self, size: usize) -> Chunks {
+        Chunks::new(self.into_generic(), size)
+    }
+
+    /// Iterator over chunks of exactly `size` nibbles, starting at the beginning of the slice.
+    ///
+    /// Any nibbles left over are available from the iterator's `remainder` method.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero.
+    fn chunks_exact(&self, size: usize) -> ChunksExact {
+        ChunksExact::new(self.into_generic(), size)
+    }
+
+    /// Iterator over chunks of `size` nibbles, starting at the end of the slice.
+    ///
+    /// The last chunk will be shorter if `size` does not divide the length of the slice.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero.
+    fn rchunks(&self, size: usize) -> RChunks {
+        RChunks::new(self.into_generic(), size)
+    }
+
+    /// Iterator over all overlapping windows of `size` nibbles.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero.
+    fn windows(&self, size: usize) -> Windows {
+        Windows::new(self.into_generic(), size)
+    }
+
+    /// Checks whether the nibbles in this slice are in ascending order.
+    fn is_sorted(&self) -> bool {
+        let mut nibbles = self.nibbles().map(u4::to_lo);
+        let mut prev = match nibbles.next() {
+            Some(nib) => nib,
+            None => return true,
+        };
+        for nib in nibbles {
+            if nib < prev {
+                return false;
             }
-        } else {
-            if self.has_right_lo() {
-                NibSliceNoL::from_slice(self.iter().as_slice()).into()
-            } else {
-                NibSliceNoBoth::from_slice(self.iter().as_slice()).into()
+            prev = nib;
+        }
+        true
+    }
+
+    /// Searches this sorted slice for the given nibble.
+    ///
+    /// If found, returns `Ok` with the index of a matching nibble; otherwise, returns `Err` with
+    /// the index where the nibble could be inserted while keeping the slice sorted.
+    fn binary_search<T: u4>(&self, nib: T) -> Result<usize, usize> {
+        let nib = nib.to_lo();
+        let (mut lo, mut hi) = (0, self.len());
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            match unsafe { self.get_unchecked(mid) }.to_lo().cmp(&nib) {
+                Ordering::Less => lo = mid + 1,
+                Ordering::Greater => hi = mid,
+                Ordering::Equal => return Ok(mid),
             }
         }
+        Err(lo)
+    }
+
+    /// Finds the index of the first occurrence of the given nibble.
+    ///
+    /// This checks sixteen nibbles at a time.
+    fn position_of<T: u4>(&self, nib: T) -> Option<usize> {
+        let offset = !self.has_left_hi() as usize;
+        let end = offset + self.len();
+        find_nib(self.iter().as_slice(), offset, end, nib.to_u4lo()).map(|idx| idx - offset)
+    }
+
+    /// Finds the index of the last occurrence of the given nibble.
+    ///
+    /// This checks sixteen nibbles at a time.
+    fn rposition_of<T: u4>(&self, nib: T) -> Option<usize> {
+        let offset = !self.has_left_hi() as usize;
+        let end = offset + self.len();
+        rfind_nib(self.iter().as_slice(), offset, end, nib.to_u4lo()).map(|idx| idx - offset)
+    }
+
+    /// Finds the index of the first occurrence of `needle` in this slice.
+    ///
+    /// The two slices may have different alignments.
+    fn find<S: ?Sized + NibSliceExt>(&self, needle: &S) -> Option<usize> {
+        self.into_generic().find_from(needle.into_generic(), 0)
+    }
+
+    /// Finds the index of the last occurrence of `needle` in this slice.
+    ///
+    /// The two slices may have different alignments.
+    fn rfind<S: ?Sized + NibSliceExt>(&self, needle: &S) -> Option<usize> {
+        self.into_generic().rfind_to(needle.into_generic(), self.len())
+    }
+
+    /// Checks whether `needle` occurs anywhere in this slice.
+    fn contains<S: ?Sized + NibSliceExt>(&self, needle: &S) -> bool {
+        self.find(needle).is_some()
+    }
+
+    /// Checks whether this slice starts with `needle`.
+    fn starts_with<S: ?Sized + NibSliceExt>(&self, needle: &S) -> bool {
+        let len = needle.len();
+        len <= self.len() && self.slice(..len) == *needle
+    }
+
+    /// Checks whether this slice ends with `needle`.
+    fn ends_with<S: ?Sized + NibSliceExt>(&self, needle: &S) -> bool {
+        let len = self.len();
+        let needle_len = needle.len();
+        needle_len <= len && self.slice(len - needle_len..) == *needle
+    }
+
+    /// Iterator over the non-overlapping occurrences of `needle` in this slice, and their
+    /// indices.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `needle` is empty.
+    fn match_indices<'a, 'b, S: ?Sized + NibSliceExt>(&'a self, needle: &'b S) -> MatchIndices<'a, 'b> {
+        MatchIndices::new(self.into_generic(), needle.into_generic())
+    }
+
+    /// Iterator over the subslices separated by occurrences of `sep`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sep` is empty.
+    fn split<'a, 'b, S: ?Sized + NibSliceExt>(&'a self, sep: &'b S) -> Split<'a, 'b> {
+        Split::new(self.into_generic(), sep.into_generic())
+    }
+
+    /// Iterator over the bytes of this slice, realigned so that the first nibble is the
+    /// high-order nibble of the first byte.
+    ///
+    /// If the slice has an odd number of nibbles, the last byte is padded with a zero low-order
+    /// nibble.
+    fn realigned_bytes(&self) -> RealignedBytes {
+        let start = !self.has_left_hi() as usize;
+        RealignedBytes::new(self.iter().as_slice(), start, start + self.len())
+    }
+
+    /// Copies this slice into a new, aligned `NibVec`.
+    ///
+    /// Unaligned slices are shifted a word at a time.
+    #[cfg(feature = "std")]
+    fn to_vec(&self) -> NibVec {
+        let len = self.len();
+        let mut inner = vec![u4x2::from_byte(0); (len + 1) >> 1];
+        copy_nibs(&mut inner, 0, self.iter().as_slice(), !self.has_left_hi() as usize, len);
+        NibVec::from_parts(inner, len & 1 == 0)
+    }
+
+    /// Copies this slice into a new vector of bytes, realigned so that the first nibble is the
+    /// high-order nibble of the first byte.
+    ///
+    /// If the slice has an odd number of nibbles, the last byte is padded with a zero low-order
+    /// nibble.
+    #[cfg(feature = "std")]
+    fn to_aligned_bytes(&self) -> Vec<u8> {
+        self.to_vec().into_byte_vec()
+    }
+
+    /// Copies this slice into a new vector of bytes, packed in the given order.
+    ///
+    /// If the slice has an odd number of nibbles, the unused half of the last byte is zero.
+    #[cfg(feature = "std")]
+    fn to_ordered_bytes<O: NibOrder>(&self) -> Vec<u8> {
+        let mut bytes = self.to_aligned_bytes();
+        O::reorder(&mut bytes);
+        bytes
+    }
+
+    /// Converts this slice into a `NibSlice`.
+    fn into_generic(&self) -> NibSlice {
+        NibSlice::from_parts(self.iter().as_slice(), self.has_left_hi(), self.has_right_lo())
     }
 
     /// Checks whether this slice is aligned to a byte boundary.
@@ -156,27 +403,255 @@ pub trait NibSliceMutExt: NibSliceExt + private::SealedMut {
         }
     }
 
-    /// Mutably gets a nibble at the given index.
-    fn get_mut(&mut self, idx: usize) -> &U4Cell {
-        get_nib_mut(self.iter_mut().into_slice(), idx)
+    /// Mutably gets a nibble at the given index, if it's in bounds.
+    fn get_mut(&mut self, idx: usize) -> Option<&U4Cell> {
+        if idx < self.len() {
+            Some(unsafe { self.get_unchecked_mut(idx) })
+        } else {
+            None
+        }
     }
 
-    /// Converts this slice into a `NibSliceMut`.
-    fn into_generic_mut(&mut self) -> NibSliceMut {
-        if self.has_left_hi() {
-            if self.has_right_lo() {
-                NibSliceFull::from_mut_slice(self.iter_mut().into_slice()).into()
-            } else {
-                NibSliceNoR::from_mut_slice(self.iter_mut().into_slice()).into()
-            }
+    /// Mutably gets a nibble at the given index without bounds checking.
+    ///
+    /// Calling this with an index that's out of bounds is undefined behaviour.
+    unsafe fn get_unchecked_mut(&mut self, idx: usize) -> &U4Cell {
+        let idx = idx + !self.has_left_hi() as usize;
+        let pair = self.iter_mut().into_slice().get_unchecked_mut(idx >> 1);
+        if idx & 1 == 0 {
+            pair.hi_mut()
         } else {
-            if self.has_right_lo() {
-                NibSliceNoL::from_mut_slice(self.iter_mut().into_slice()).into()
-            } else {
-                NibSliceNoBoth::from_mut_slice(self.iter_mut().into_slice()).into()
-            }
+            pair.lo_mut()
         }
     }
+
+    /// Mutably gets the first nibble of the slice, if it's not empty.
+    fn first_mut(&mut self) -> Option<&U4Cell> {
+        self.get_mut(0)
+    }
+
+    /// Mutably gets the last nibble of the slice, if it's not empty.
+    fn last_mut(&mut self) -> Option<&U4Cell> {
+        match self.len().checked_sub(1) {
+            Some(idx) => self.get_mut(idx),
+            None => None,
+        }
+    }
+
+    /// Mutably gets the nibbles in the given range as a subslice.
+    ///
+    /// The kind of slice returned depends on whether the range starts and ends on a pair
+    /// boundary.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds or its start is greater than its end.
+    fn slice_mut<R: RangeBounds<usize>>(&mut self, range: R) -> NibSliceMut {
+        let (start, end) = nib_range(range, self.len());
+        let offset = !self.has_left_hi() as usize;
+        let (pairs, has_left_hi, has_right_lo) = pair_range(start + offset, end + offset);
+        let slice = &mut self.iter_mut().into_slice()[pairs];
+        NibSliceMut::from_parts(slice, has_left_hi, has_right_lo)
+    }
+
+    /// Copies all nibbles from `src` into this slice.
+    ///
+    /// The two slices may have different alignments.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the two slices have different lengths.
+    fn copy_from_nibs<S: ?Sized + NibSliceExt>(&mut self, src: &S) {
+        let len = self.len();
+        assert_eq!(len, src.len(), "source and destination slices have different lengths");
+        let dst_start = !self.has_left_hi() as usize;
+        let src_start = !src.has_left_hi() as usize;
+        copy_nibs(self.iter_mut().into_slice(), dst_start, src.iter().as_slice(), src_start, len);
+    }
+
+    /// Swaps all nibbles in this slice with those in `other`.
+    ///
+    /// The two slices may have different alignments.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the two slices have different lengths.
+    fn swap_with<S: ?Sized + NibSliceMutExt>(&mut self, other: &mut S) {
+        const BUF_LEN: usize = 32;
+
+        let len = self.len();
+        assert_eq!(len, other.len(), "slices to swap have different lengths");
+        let self_start = !self.has_left_hi() as usize;
+        let other_start = !other.has_left_hi() as usize;
+        let this = self.iter_mut().into_slice();
+        let other = other.iter_mut().into_slice();
+
+        let mut buf = [u4x2::from_byte(0); BUF_LEN];
+        let mut idx = 0;
+        while idx < len {
+            let count = if len - idx < BUF_LEN << 1 { len - idx } else { BUF_LEN << 1 };
+            copy_nibs(&mut buf, 0, this, self_start + idx, count);
+            copy_nibs(this, self_start + idx, other, other_start + idx, count);
+            copy_nibs(other, other_start + idx, &buf, 0, count);
+            idx += count;
+        }
+    }
+
+    /// Sets every nibble in this slice to the given nibble.
+    fn fill<T: u4>(&mut self, nib: T) {
+        let len = self.len();
+        let start = !self.has_left_hi() as usize;
+        fill_nibs(self.iter_mut().into_slice(), start, len, nib.to_u4lo());
+    }
+
+    /// Sets every nibble in this slice by repeatedly calling the given function.
+    fn fill_with<T: u4, F: FnMut() -> T>(&mut self, mut f: F) {
+        for cell in self.nibbles_mut() {
+            cell.set_from_lo(f().to_u4lo());
+        }
+    }
+
+    /// Reverses the order of the nibbles in this slice.
+    fn reverse(&mut self) {
+        let has_left_hi = self.has_left_hi();
+        let has_right_lo = self.has_right_lo();
+        reverse_nibs(self.iter_mut().into_slice(), has_left_hi, has_right_lo);
+    }
+
+    /// Rotates the slice in place so that the nibble at index `mid` becomes the first nibble.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mid > len`.
+    fn rotate_left(&mut self, mid: usize) {
+        let len = self.len();
+        assert!(mid <= len, "rotation {} out of range for slice of length {}", mid, len);
+        self.slice_mut(..mid).reverse();
+        self.slice_mut(mid..).reverse();
+        self.reverse();
+    }
+
+    /// Rotates the slice in place so that the last `k` nibbles become the first nibbles.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `k > len`.
+    fn rotate_right(&mut self, k: usize) {
+        let len = self.len();
+        assert!(k <= len, "rotation {} out of range for slice of length {}", k, len);
+        self.rotate_left(len - k);
+    }
+
+    /// Sorts the nibbles in this slice in ascending order.
+    ///
+    /// Since there are only sixteen possible nibbles, this is done with a counting sort.
+    fn sort(&mut self) {
+        let mut counts = [0usize; 16];
+        for nib in self.nibbles() {
+            counts[nib.to_lo() as usize] += 1;
+        }
+        let mut start = 0;
+        for (nib, &count) in counts.iter().enumerate() {
+            self.slice_mut(start..start + count).fill(u4lo::from_lo(nib as u8));
+            start += count;
+        }
+    }
+
+    /// Sorts the nibbles in this slice in ascending order.
+    ///
+    /// Because equal nibbles are indistinguishable, this is identical to `sort`.
+    fn sort_unstable(&mut self) {
+        self.sort()
+    }
+
+    /// Sorts the nibbles in this slice by the given key, keeping nibbles with equal keys in their
+    /// original order.
+    ///
+    /// Unlike `slice::sort_by_key`, the key function is called exactly once for each of the
+    /// sixteen possible nibbles, in ascending order, rather than once per element. It's called
+    /// even for nibbles that don't appear in the slice, including when the slice is empty.
+    #[cfg(feature = "std")]
+    fn sort_by_key<K: Ord, F: FnMut(u4lo) -> K>(&mut self, mut f: F) {
+        let keys: Vec<K> = (0..16).map(|nib| f(u4lo::from_lo(nib))).collect();
+        let mut nibs: Vec<u8> = self.nibbles().map(u4::to_lo).collect();
+        nibs.sort_by(|&a, &b| keys[a as usize].cmp(&keys[b as usize]));
+        for (cell, nib) in self.nibbles_mut().zip(nibs) {
+            cell.set_from_lo(u4lo::from_lo(nib));
+        }
+    }
+
+    /// Views this slice as a slice of nibble cells.
+    ///
+    /// Unlike `NibSliceMut`, the cells can be split between the two nibbles of a pair.
+    fn as_cells(&mut self) -> NibSliceCell {
+        let has_left_hi = self.has_left_hi();
+        let has_right_lo = self.has_right_lo();
+        let slice = Cell::from_mut(self.iter_mut().into_slice()).as_slice_of_cells();
+        NibSliceCell::from_parts(slice, has_left_hi, has_right_lo)
+    }
+
+    /// Mutably divides the slice into two at the given index.
+    ///
+    /// Because the index may fall between the two nibbles of a pair, the halves are returned as
+    /// cells.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mid > len`.
+    fn split_at_mut(&mut self, mid: usize) -> (NibSliceCell, NibSliceCell) {
+        self.as_cells().split_at(mid)
+    }
+
+    /// Mutably splits off the first nibble of the slice, if it's not empty.
+    fn split_first_mut(&mut self) -> Option<(&U4Cell, NibSliceCell)> {
+        let cells = self.as_cells();
+        let len = cells.len();
+        cells.cell(0).map(|first| (first, cells.sub(1, len)))
+    }
+
+    /// Mutably splits off the last nibble of the slice, if it's not empty.
+    fn split_last_mut(&mut self) -> Option<(&U4Cell, NibSliceCell)> {
+        let cells = self.as_cells();
+        let len = cells.len();
+        len.checked_sub(1)
+            .and_then(|idx| cells.cell(idx))
+            .map(|last| (last, cells.sub(0, len - 1)))
+    }
+
+    /// Mutable iterator over chunks of `size` nibbles, starting at the beginning of the slice.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero.
+    fn chunks_mut(&mut self, size: usize) -> ChunksMut {
+        ChunksMut::new(self.as_cells(), size)
+    }
+
+    /// Mutable iterator over chunks of exactly `size` nibbles, starting at the beginning of the
+    /// slice.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero.
+    fn chunks_exact_mut(&mut self, size: usize) -> ChunksExactMut {
+        ChunksExactMut::new(self.as_cells(), size)
+    }
+
+    /// Mutable iterator over chunks of `size` nibbles, starting at the end of the slice.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero.
+    fn rchunks_mut(&mut self, size: usize) -> RChunksMut {
+        RChunksMut::new(self.as_cells(), size)
+    }
+
+    /// Converts this slice into a `NibSliceMut`.
+    fn into_generic_mut(&mut self) -> NibSliceMut {
+        let has_left_hi = self.has_left_hi();
+        let has_right_lo = self.has_right_lo();
+        NibSliceMut::from_parts(self.iter_mut().into_slice(), has_left_hi, has_right_lo)
+    }
 }
 
 /// Nibble slice which only contains complete pairs.
@@ -287,7 +762,42 @@ impl private::SealedMut for NibSliceNoBoth {
 impl NibSliceExt for NibSliceNoBoth {}
 impl NibSliceMutExt for NibSliceNoBoth {}
 
+/// Nibbles which can be used to look up owned nibbles in a map.
+///
+/// Every nibble slice implements this, and `NibVec` and `NibBox` borrow as `dyn NibKey`. So a
+/// `HashMap` keyed by either can be searched with any slice, aligned or not, without allocating:
+/// `map.get(&slice as &dyn NibKey)`.
+pub trait NibKey {
+    /// Views the key as a slice.
+    fn as_key_slice(&self) -> NibSlice<'_>;
+}
+impl<S: ?Sized + NibSliceExt> NibKey for S {
+    #[inline]
+    fn as_key_slice(&self) -> NibSlice<'_> {
+        self.into_generic()
+    }
+}
+
+#[cfg(feature = "std")]
+macro_rules! do_to_owned {
+    ($($t:ident)*) => {
+        $(
+            impl ToOwned for $t {
+                type Owned = Box<$t>;
+                fn to_owned(&self) -> Box<$t> {
+                    let inner = self.inner.to_vec().into_boxed_slice();
+                    unsafe { Box::from_raw(Box::into_raw(inner) as *mut $t) }
+                }
+            }
+        )*
+    }
+}
+
+#[cfg(feature = "std")]
+do_to_owned! { NibSliceFull NibSliceNoR NibSliceNoL NibSliceNoBoth }
+
 /// Reference to a nibble slice that's aligned to a byte boundary.
+#[derive(Clone, Copy)]
 pub enum NibSliceAligned<'a> {
     /// An aligned slice with an even number of nibbles.
     Even(&'a NibSliceFull),
@@ -372,6 +882,7 @@ impl<'a> NibSliceExt for NibSliceAlignedMut<'a> {}
 impl<'a> NibSliceMutExt for NibSliceAlignedMut<'a> {}
 
 /// Reference to a nibble slice that's not aligned to a byte boundary.
+#[derive(Clone, Copy)]
 pub enum NibSliceUnaligned<'a> {
     /// An unaligned slice with an even number of nibbles.
     Even(&'a NibSliceNoBoth),
@@ -456,6 +967,7 @@ impl<'a> NibSliceExt for NibSliceUnalignedMut<'a> {}
 impl<'a> NibSliceMutExt for NibSliceUnalignedMut<'a> {}
 
 /// Reference to a nibble slice with an even number of nibbles.
+#[derive(Clone, Copy)]
 pub enum NibSliceEven<'a> {
     /// An aligned slice with an even number of nibbles.
     Aligned(&'a NibSliceFull),
@@ -544,6 +1056,7 @@ impl<'a> NibSliceExt for NibSliceEvenMut<'a> {}
 impl<'a> NibSliceMutExt for NibSliceEvenMut<'a> {}
 
 /// Reference to a nibble slice with an odd number of nibbles.
+#[derive(Clone, Copy)]
 pub enum NibSliceOdd<'a> {
     /// An aligned slice with an odd number of nibbles.
     Aligned(&'a NibSliceNoR),
@@ -632,6 +1145,7 @@ impl<'a> NibSliceExt for NibSliceOddMut<'a> {}
 impl<'a> NibSliceMutExt for NibSliceOddMut<'a> {}
 
 /// Reference to a nibble slice.
+#[derive(Clone, Copy)]
 pub enum NibSlice<'a> {
     /// A slice with both sides.
     Full(&'a NibSliceFull),
@@ -698,6 +1212,99 @@ impl<'a> From<NibSliceOdd<'a>> for NibSlice<'a> {
     }
 }
 impl<'a> NibSlice<'a> {
+    pub(crate) fn from_parts(slice: &'a [u4x2], has_left_hi: bool, has_right_lo: bool) -> Self {
+        match (has_left_hi, has_right_lo) {
+            (true, true) => NibSliceFull::from_slice(slice).into(),
+            (false, true) => NibSliceNoL::from_slice(slice).into(),
+            (true, false) => NibSliceNoR::from_slice(slice).into(),
+            (false, false) => NibSliceNoBoth::from_slice(slice).into(),
+        }
+    }
+
+    /// Views the given range of nibbles in a byte slice, without copying.
+    ///
+    /// The bytes are read high-order nibble first; use `NibBytes::from_ordered` to view bytes packed
+    /// in another order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn from_bytes<R: RangeBounds<usize>>(bytes: &'a [u8], range: R) -> Self {
+        let (start, end) = nib_range(range, bytes.len() << 1);
+        let (pairs, has_left_hi, has_right_lo) = pair_range(start, end);
+        NibSlice::from_parts(&as_pairs(bytes)[pairs], has_left_hi, has_right_lo)
+    }
+
+    pub(crate) fn pairs(&self) -> &'a [u4x2] {
+        match *self {
+            NibSlice::Full(s) => &s.inner,
+            NibSlice::NoL(s) => &s.inner,
+            NibSlice::NoR(s) => &s.inner,
+            NibSlice::NoBoth(s) => &s.inner,
+        }
+    }
+
+    pub(crate) fn sub(&self, start: usize, end: usize) -> NibSlice<'a> {
+        let offset = !private::Sealed::has_left_hi(self) as usize;
+        let (pairs, has_left_hi, has_right_lo) = pair_range(start + offset, end + offset);
+        NibSlice::from_parts(&self.pairs()[pairs], has_left_hi, has_right_lo)
+    }
+
+    /// Finds the first occurrence of `needle` starting at or after `from`.
+    pub(crate) fn find_from(&self, needle: NibSlice, from: usize) -> Option<usize> {
+        let len = self.len();
+        let needle_len = needle.len();
+        let first = match needle.first() {
+            Some(first) => first.to_u4lo(),
+            None => return if from <= len { Some(from) } else { None },
+        };
+        if needle_len > len {
+            return None;
+        }
+
+        let offset = !private::Sealed::has_left_hi(self) as usize;
+        let end = len - needle_len + 1;
+        let mut idx = from;
+        while idx < end {
+            idx = find_nib(self.pairs(), idx + offset, end + offset, first)? - offset;
+            if self.sub(idx, idx + needle_len) == needle {
+                return Some(idx);
+            }
+            idx += 1;
+        }
+        None
+    }
+
+    /// Finds the last occurrence of `needle` ending at or before `to`.
+    pub(crate) fn rfind_to(&self, needle: NibSlice, to: usize) -> Option<usize> {
+        let needle_len = needle.len();
+        let last = match needle.last() {
+            Some(last) => last.to_u4lo(),
+            None => return Some(to),
+        };
+        if needle_len > to {
+            return None;
+        }
+
+        let offset = !private::Sealed::has_left_hi(self) as usize;
+        let start = needle_len - 1;
+        let mut idx = to;
+        while idx > start {
+            idx = rfind_nib(self.pairs(), start + offset, idx + offset, last)? - offset;
+            if self.sub(idx + 1 - needle_len, idx + 1) == needle {
+                return Some(idx + 1 - needle_len);
+            }
+        }
+        None
+    }
+
+    /// Iterator over the nibbles in this slice, borrowed for as long as the slice itself.
+    pub fn into_nibbles(self) -> Nibbles<'a> {
+        let has_left_hi = private::Sealed::has_left_hi(&self);
+        let has_right_lo = private::Sealed::has_right_lo(&self);
+        Nibbles::new(NibblePairs::new(self.pairs().iter()), has_left_hi, has_right_lo)
+    }
+
     /// Converts this slice into an aligned version.
     pub fn as_aligned(&self) -> Option<NibSliceAligned<'a>> {
         match *self {
@@ -833,6 +1440,28 @@ impl<'a> From<NibSliceOddMut<'a>> for NibSliceMut<'a> {
     }
 }
 impl<'a> NibSliceMut<'a> {
+    pub(crate) fn from_parts(slice: &'a mut [u4x2], has_left_hi: bool, has_right_lo: bool) -> Self {
+        match (has_left_hi, has_right_lo) {
+            (true, true) => NibSliceFull::from_mut_slice(slice).into(),
+            (false, true) => NibSliceNoL::from_mut_slice(slice).into(),
+            (true, false) => NibSliceNoR::from_mut_slice(slice).into(),
+            (false, false) => NibSliceNoBoth::from_mut_slice(slice).into(),
+        }
+    }
+
+    /// Mutably views the given range of nibbles in a byte slice, without copying.
+    ///
+    /// Like `NibSlice::from_bytes`, this is always high-order nibble first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn from_bytes_mut<R: RangeBounds<usize>>(bytes: &'a mut [u8], range: R) -> Self {
+        let (start, end) = nib_range(range, bytes.len() << 1);
+        let (pairs, has_left_hi, has_right_lo) = pair_range(start, end);
+        NibSliceMut::from_parts(&mut as_pairs_mut(bytes)[pairs], has_left_hi, has_right_lo)
+    }
+
     /// Converts this slice into an aligned version.
     pub fn into_aligned(self) -> Option<NibSliceAlignedMut<'a>> {
         match self {
@@ -912,3 +1541,656 @@ impl<'a> private::SealedMut for NibSliceMut<'a> {
 }
 impl<'a> NibSliceExt for NibSliceMut<'a> {}
 impl<'a> NibSliceMutExt for NibSliceMut<'a> {}
+
+/// Reference to a nibble slice whose alignment is only known at runtime.
+///
+/// This works like `NibSlice`, but stores the omitted nibbles as flags instead of choosing
+/// between four slice types, which makes it easier to pass around in generic code. Any slice can
+/// be converted into one of these for free.
+#[derive(Clone, Copy)]
+pub struct NibSliceRef<'a> {
+    inner: &'a [u4x2],
+    has_left_hi: bool,
+    has_right_lo: bool,
+}
+impl<'a> NibSliceRef<'a> {
+    /// Whether the slice starts on the low-order nibble of a pair.
+    pub fn start_odd(&self) -> bool {
+        !self.has_left_hi
+    }
+
+    /// Whether the slice ends on the high-order nibble of a pair.
+    pub fn end_odd(&self) -> bool {
+        !self.has_right_lo
+    }
+}
+impl<'a, T: ?Sized + NibSliceExt> From<&'a T> for NibSliceRef<'a> {
+    fn from(slice: &'a T) -> Self {
+        NibSliceRef {
+            inner: slice.iter().as_slice(),
+            has_left_hi: slice.has_left_hi(),
+            has_right_lo: slice.has_right_lo(),
+        }
+    }
+}
+impl<'a> From<NibSlice<'a>> for NibSliceRef<'a> {
+    fn from(slice: NibSlice<'a>) -> Self {
+        NibSliceRef {
+            inner: slice.pairs(),
+            has_left_hi: private::Sealed::has_left_hi(&slice),
+            has_right_lo: private::Sealed::has_right_lo(&slice),
+        }
+    }
+}
+impl<'a> From<NibSliceRef<'a>> for NibSlice<'a> {
+    fn from(slice: NibSliceRef<'a>) -> Self {
+        NibSlice::from_parts(slice.inner, slice.has_left_hi, slice.has_right_lo)
+    }
+}
+impl<'a> private::Sealed for NibSliceRef<'a> {
+    #[inline(always)]
+    fn has_left_hi(&self) -> bool { self.has_left_hi }
+    #[inline(always)]
+    fn has_right_lo(&self) -> bool { self.has_right_lo }
+    #[inline(always)]
+    fn iter(&self) -> slice::Iter<u4x2> { self.inner.iter() }
+}
+impl<'a> NibSliceExt for NibSliceRef<'a> {}
+
+/// Mutable reference to a nibble slice whose alignment is only known at runtime.
+///
+/// This is the mutable counterpart to `NibSliceRef`.
+pub struct NibSliceRefMut<'a> {
+    inner: &'a mut [u4x2],
+    has_left_hi: bool,
+    has_right_lo: bool,
+}
+impl<'a> NibSliceRefMut<'a> {
+    /// Whether the slice starts on the low-order nibble of a pair.
+    pub fn start_odd(&self) -> bool {
+        !self.has_left_hi
+    }
+
+    /// Whether the slice ends on the high-order nibble of a pair.
+    pub fn end_odd(&self) -> bool {
+        !self.has_right_lo
+    }
+
+    /// Reborrows this slice immutably.
+    pub fn as_shared(&self) -> NibSliceRef {
+        NibSliceRef { inner: self.inner, has_left_hi: self.has_left_hi, has_right_lo: self.has_right_lo }
+    }
+
+    /// Reborrows this slice mutably.
+    pub fn reborrow(&mut self) -> NibSliceRefMut {
+        NibSliceRefMut { inner: self.inner, has_left_hi: self.has_left_hi, has_right_lo: self.has_right_lo }
+    }
+}
+impl<'a, T: ?Sized + NibSliceMutExt> From<&'a mut T> for NibSliceRefMut<'a> {
+    fn from(slice: &'a mut T) -> Self {
+        let has_left_hi = slice.has_left_hi();
+        let has_right_lo = slice.has_right_lo();
+        NibSliceRefMut { inner: slice.iter_mut().into_slice(), has_left_hi, has_right_lo }
+    }
+}
+impl<'a> From<NibSliceMut<'a>> for NibSliceRefMut<'a> {
+    fn from(slice: NibSliceMut<'a>) -> Self {
+        let has_left_hi = private::Sealed::has_left_hi(&slice);
+        let has_right_lo = private::Sealed::has_right_lo(&slice);
+        let inner = match slice {
+            NibSliceMut::Full(s) => &mut s.inner,
+            NibSliceMut::NoL(s) => &mut s.inner,
+            NibSliceMut::NoR(s) => &mut s.inner,
+            NibSliceMut::NoBoth(s) => &mut s.inner,
+        };
+        NibSliceRefMut { inner, has_left_hi, has_right_lo }
+    }
+}
+impl<'a> From<NibSliceRefMut<'a>> for NibSliceMut<'a> {
+    fn from(slice: NibSliceRefMut<'a>) -> Self {
+        NibSliceMut::from_parts(slice.inner, slice.has_left_hi, slice.has_right_lo)
+    }
+}
+impl<'a> private::Sealed for NibSliceRefMut<'a> {
+    #[inline(always)]
+    fn has_left_hi(&self) -> bool { self.has_left_hi }
+    #[inline(always)]
+    fn has_right_lo(&self) -> bool { self.has_right_lo }
+    #[inline(always)]
+    fn iter(&self) -> slice::Iter<u4x2> { self.inner.iter() }
+}
+impl<'a> private::SealedMut for NibSliceRefMut<'a> {
+    #[inline(always)]
+    fn iter_mut(&mut self) -> slice::IterMut<u4x2> { self.inner.iter_mut() }
+}
+impl<'a> NibSliceExt for NibSliceRefMut<'a> {}
+impl<'a> NibSliceMutExt for NibSliceRefMut<'a> {}
+
+macro_rules! do_ref {
+    ($($t:ident $tmut:ident;)*) => {
+        $(
+            impl<'a> From<$t<'a>> for NibSliceRef<'a> {
+                fn from(slice: $t<'a>) -> Self {
+                    NibSlice::from(slice).into()
+                }
+            }
+            impl<'a> From<$tmut<'a>> for NibSliceRefMut<'a> {
+                fn from(slice: $tmut<'a>) -> Self {
+                    NibSliceMut::from(slice).into()
+                }
+            }
+        )*
+    }
+}
+
+do_ref! {
+    NibSliceAligned NibSliceAlignedMut;
+    NibSliceUnaligned NibSliceUnalignedMut;
+    NibSliceEven NibSliceEvenMut;
+    NibSliceOdd NibSliceOddMut;
+}
+
+/// Shared reference to a slice of nibbles which may be mutated through cells.
+///
+/// Unlike `NibSliceMut`, this may be split between the two nibbles of a pair, since every
+/// change goes through a `Cell` and only ever touches a single nibble.
+///
+/// The nibbles are read high-order nibble first by default, but byte cells packed in another order
+/// can be viewed with `from_ordered_byte_cells`.
+pub struct NibSliceCell<'a, O: NibOrder = HiFirst> {
+    inner: &'a [Cell<u4x2>],
+    has_left_hi: bool,
+    has_right_lo: bool,
+    order: PhantomData<O>,
+}
+impl<'a, O: NibOrder> Clone for NibSliceCell<'a, O> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<'a, O: NibOrder> Copy for NibSliceCell<'a, O> {}
+impl<'a> NibSliceCell<'a> {
+    /// Views a slice of byte cells as nibbles.
+    pub fn from_byte_cells(cells: &'a [Cell<u8>]) -> Self {
+        NibSliceCell::from_ordered_byte_cells(cells)
+    }
+
+    /// Views a cell holding a slice of pairs as nibbles.
+    pub fn from_cell(cell: &'a Cell<[u4x2]>) -> Self {
+        NibSliceCell::from_parts(cell.as_slice_of_cells(), true, true)
+    }
+}
+impl<'a, O: NibOrder> NibSliceCell<'a, O> {
+    pub(crate) fn from_parts(inner: &'a [Cell<u4x2>], has_left_hi: bool, has_right_lo: bool) -> Self {
+        NibSliceCell { inner, has_left_hi, has_right_lo, order: PhantomData }
+    }
+
+    /// Views a slice of byte cells, packed in this slice's order, as nibbles.
+    pub fn from_ordered_byte_cells(cells: &'a [Cell<u8>]) -> Self {
+        let inner = unsafe { &*(cells as *const [Cell<u8>] as *const [Cell<u4x2>]) };
+        NibSliceCell::from_parts(inner, true, true)
+    }
+
+    pub(crate) fn sub(&self, start: usize, end: usize) -> NibSliceCell<'a, O> {
+        let offset = !self.has_left_hi as usize;
+        let (pairs, has_left_hi, has_right_lo) = pair_range(start + offset, end + offset);
+        NibSliceCell::from_parts(&self.inner[pairs], has_left_hi, has_right_lo)
+    }
+
+    /// Gets the length of the slice.
+    pub fn len(&self) -> usize {
+        let hi = !self.has_left_hi as usize;
+        let lo = !self.has_right_lo as usize;
+        (self.inner.len() << 1).saturating_sub(hi + lo)
+    }
+
+    /// Checks if the slice is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Gets the cell for the nibble at the given index, if it's in bounds.
+    pub fn cell(&self, idx: usize) -> Option<&'a U4Cell> {
+        if idx >= self.len() {
+            return None;
+        }
+        let idx = idx + !self.has_left_hi as usize;
+        let pair = &self.inner[idx >> 1] as *const Cell<u4x2>;
+        Some(if is_hi::<O>(idx) {
+            unsafe { &*(pair as *const U4HiCell) }
+        } else {
+            unsafe { &*(pair as *const U4LoCell) }
+        })
+    }
+
+    /// Gets the nibble at the given index, if it's in bounds.
+    pub fn get<T: u4>(&self, idx: usize) -> Option<T> {
+        self.cell(idx).map(|cell| T::from_lo(cell.get_lo().to_lo()))
+    }
+
+    /// Sets the nibble at the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn set<T: u4>(&self, idx: usize, nib: T) {
+        let len = self.len();
+        match self.cell(idx) {
+            Some(cell) => cell.set_from_lo(nib.to_u4lo()),
+            None => panic!("index {} out of range for slice of length {}", idx, len),
+        }
+    }
+
+    /// Sets the nibble at the given index, returning the old one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn replace<T: u4>(&self, idx: usize, nib: T) -> T {
+        let old = self.get(idx);
+        self.set(idx, nib);
+        old.unwrap_or_else(|| unreachable!())
+    }
+
+    /// Swaps the nibbles at the given indices.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either index is out of bounds.
+    pub fn swap(&self, a: usize, b: usize) {
+        let nib: u4lo = self.replace(a, self.get::<u4lo>(b).unwrap_or_else(|| {
+            panic!("index {} out of range for slice of length {}", b, self.len())
+        }));
+        self.set(b, nib);
+    }
+
+    /// Copies this slice into a new, aligned `NibVec`.
+    #[cfg(feature = "std")]
+    pub fn to_vec(&self) -> NibVec {
+        self.nibbles().map(|cell| cell.get_lo()).collect()
+    }
+
+    /// Iterator over the cells for the nibbles in this slice.
+    pub fn nibbles(&self) -> NibbleCells<'a, O> {
+        NibbleCells::new(*self)
+    }
+
+    /// Gets the nibbles in the given range as a subslice.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds or its start is greater than its end.
+    pub fn slice<R: RangeBounds<usize>>(&self, range: R) -> NibSliceCell<'a, O> {
+        let (start, end) = nib_range(range, self.len());
+        self.sub(start, end)
+    }
+
+    /// Divides the slice into two at the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mid > len`.
+    pub fn split_at(&self, mid: usize) -> (NibSliceCell<'a, O>, NibSliceCell<'a, O>) {
+        let len = self.len();
+        assert!(mid <= len, "split index {} out of range for slice of length {}", mid, len);
+        (self.sub(0, mid), self.sub(mid, len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use order::LoFirst;
+
+    fn pairs(bytes: &[u8]) -> &[u4x2] {
+        unsafe { &*(bytes as *const [u8] as *const [u4x2]) }
+    }
+
+    fn pairs_mut(bytes: &mut [u8]) -> &mut [u4x2] {
+        unsafe { &mut *(bytes as *mut [u8] as *mut [u4x2]) }
+    }
+
+    fn nibs<S: ?Sized + NibSliceExt>(slice: &S) -> Vec<u8> {
+        slice.nibbles().map(u4::to_lo).collect()
+    }
+
+    #[test]
+    fn len() {
+        let bytes = [0x01, 0x23, 0x45];
+        assert_eq!(NibSliceFull::from_slice(pairs(&bytes)).len(), 6);
+        assert_eq!(NibSliceNoL::from_slice(pairs(&bytes)).len(), 5);
+        assert_eq!(NibSliceNoR::from_slice(pairs(&bytes)).len(), 5);
+        assert_eq!(NibSliceNoBoth::from_slice(pairs(&bytes)).len(), 4);
+        assert!(NibSliceNoBoth::from_slice(pairs(&bytes[..1])).is_empty());
+        assert!(NibSliceNoR::from_slice(pairs(&[])).is_empty());
+    }
+
+    #[test]
+    fn get() {
+        let bytes = [0x01, 0x23, 0x45];
+        let no_l = NibSliceNoL::from_slice(pairs(&bytes));
+        assert_eq!(no_l.get(0).map(u4::to_lo), Some(1));
+        assert_eq!(no_l.get(4).map(u4::to_lo), Some(5));
+        assert!(no_l.get(5).is_none());
+        assert_eq!(no_l.first().map(u4::to_lo), Some(1));
+        let no_both = NibSliceNoBoth::from_slice(pairs(&bytes));
+        assert_eq!(no_both.last().map(u4::to_lo), Some(4));
+        assert!(no_both.get(4).is_none());
+        assert!(NibSliceNoBoth::from_slice(pairs(&bytes[..1])).first().is_none());
+    }
+
+    #[test]
+    fn get_mut() {
+        let mut bytes = [0x01, 0x23];
+        {
+            let no_both = NibSliceNoBoth::from_mut_slice(pairs_mut(&mut bytes));
+            no_both.first_mut().unwrap().set_from_lo(u4lo::from_lo(0xA));
+            no_both.last_mut().unwrap().set_from_lo(u4lo::from_lo(0xB));
+            assert!(no_both.get_mut(2).is_none());
+        }
+        assert_eq!(bytes, [0x0A, 0xB3]);
+    }
+
+    #[test]
+    fn slice() {
+        let bytes = [0x01, 0x23, 0x45, 0x67];
+        let full = NibSliceFull::from_slice(pairs(&bytes));
+        assert_eq!(nibs(&full.slice(..)), [0, 1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(nibs(&full.slice(3..6)), [3, 4, 5]);
+        assert_eq!(nibs(&full.slice(2..=5)), [2, 3, 4, 5]);
+        assert_eq!(nibs(&full.slice(5..)), [5, 6, 7]);
+        assert!(full.slice(3..3).is_empty());
+
+        match full.slice(2..4) { NibSlice::Full(_) => (), _ => panic!("expected full") }
+        match full.slice(3..6) { NibSlice::NoL(_) => (), _ => panic!("expected no left") }
+        match full.slice(2..5) { NibSlice::NoR(_) => (), _ => panic!("expected no right") }
+        match full.slice(1..5) { NibSlice::NoBoth(_) => (), _ => panic!("expected no both") }
+
+        let no_l = NibSliceNoL::from_slice(pairs(&bytes));
+        assert_eq!(nibs(&no_l.slice(..2)), [1, 2]);
+        assert_eq!(nibs(&no_l.slice(1..4).slice(1..)), [3, 4]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn slice_out_of_bounds() {
+        let bytes = [0x01, 0x23];
+        NibSliceNoBoth::from_slice(pairs(&bytes)).slice(1..3);
+    }
+
+    #[test]
+    fn split() {
+        let bytes = [0x01, 0x23, 0x45];
+        let no_l = NibSliceNoL::from_slice(pairs(&bytes));
+        let (left, right) = no_l.split_at(2);
+        assert_eq!((nibs(&left), nibs(&right)), (vec![1, 2], vec![3, 4, 5]));
+        let (first, rest) = no_l.split_first().unwrap();
+        assert_eq!((first.to_lo(), nibs(&rest)), (1, vec![2, 3, 4, 5]));
+        let (last, rest) = no_l.split_last().unwrap();
+        assert_eq!((last.to_lo(), nibs(&rest)), (5, vec![1, 2, 3, 4]));
+        assert!(no_l.slice(..0).split_first().is_none());
+    }
+
+    #[test]
+    fn chunks() {
+        let bytes = [0x01, 0x23, 0x45, 0x67];
+        let no_both = NibSliceNoBoth::from_slice(pairs(&bytes));
+        let chunks: Vec<_> = no_both.chunks(4).map(|c| nibs(&c)).collect();
+        assert_eq!(chunks, [vec![1, 2, 3, 4], vec![5, 6]]);
+        let chunks: Vec<_> = no_both.chunks(4).rev().map(|c| nibs(&c)).collect();
+        assert_eq!(chunks, [vec![5, 6], vec![1, 2, 3, 4]]);
+        let rchunks: Vec<_> = no_both.rchunks(4).map(|c| nibs(&c)).collect();
+        assert_eq!(rchunks, [vec![3, 4, 5, 6], vec![1, 2]]);
+        let mut exact = no_both.chunks_exact(4);
+        assert_eq!(exact.len(), 1);
+        assert_eq!(exact.next().map(|c| nibs(&c)), Some(vec![1, 2, 3, 4]));
+        assert!(exact.next().is_none());
+        assert_eq!(nibs(&exact.remainder()), [5, 6]);
+        let windows: Vec<_> = no_both.windows(5).map(|c| nibs(&c)).collect();
+        assert_eq!(windows, [vec![1, 2, 3, 4, 5], vec![2, 3, 4, 5, 6]]);
+    }
+
+    #[test]
+    fn split_mut() {
+        let mut bytes = [0x01, 0x23, 0x45];
+        {
+            let full = NibSliceFull::from_mut_slice(pairs_mut(&mut bytes));
+            for (i, chunk) in full.chunks_mut(3).enumerate() {
+                for cell in chunk.nibbles() {
+                    cell.set_from_lo(u4lo::from_lo(i as u8));
+                }
+            }
+        }
+        assert_eq!(bytes, [0x00, 0x01, 0x11]);
+        {
+            let full = NibSliceFull::from_mut_slice(pairs_mut(&mut bytes));
+            let (left, right) = full.split_at_mut(3);
+            left.set(2, u4lo::from_lo(0xA));
+            right.set(0, u4lo::from_lo(0xB));
+        }
+        assert_eq!(bytes, [0x00, 0xAB, 0x11]);
+    }
+
+    #[test]
+    fn copy_from_nibs() {
+        let src: Vec<u8> = (0..24u8).map(|i| i.wrapping_mul(37) + 1).collect();
+        let src = pairs(&src);
+        for src_start in 0..4 {
+            for dst_start in 0..4 {
+                for len in 0..40 {
+                    let mut dst = [0u8; 24];
+                    {
+                        let dst = NibSliceFull::from_mut_slice(pairs_mut(&mut dst));
+                        let src = NibSliceFull::from_slice(src).slice(src_start..src_start + len);
+                        dst.slice_mut(dst_start..dst_start + len).copy_from_nibs(&src);
+                    }
+                    let expected: Vec<u8> = (0..48)
+                        .map(|i| if i >= dst_start && i < dst_start + len {
+                            NibSliceFull::from_slice(src).get(i - dst_start + src_start).unwrap().to_lo()
+                        } else {
+                            0
+                        })
+                        .collect();
+                    assert_eq!(nibs(NibSliceFull::from_slice(pairs(&dst))), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn swap_with() {
+        let mut left = [0x01u8; 40];
+        let mut right = [0xABu8; 40];
+        {
+            let left = NibSliceNoBoth::from_mut_slice(pairs_mut(&mut left));
+            let mut right = NibSliceFull::from_mut_slice(pairs_mut(&mut right)).slice_mut(..78);
+            left.swap_with(&mut right);
+        }
+        assert_eq!(left[0], 0x0A);
+        assert!(left[1..39].iter().all(|&b| b == 0xBA));
+        assert_eq!(left[39], 0xB1);
+        assert!(right[..39].iter().all(|&b| b == 0x10));
+        assert_eq!(right[39], 0xAB);
+    }
+
+    #[test]
+    fn fill() {
+        let mut bytes = [0x00, 0x00, 0x00];
+        NibSliceNoBoth::from_mut_slice(pairs_mut(&mut bytes)).fill(u4lo::from_lo(7));
+        assert_eq!(bytes, [0x07, 0x77, 0x70]);
+        let mut n = 0;
+        NibSliceNoR::from_mut_slice(pairs_mut(&mut bytes)).fill_with(|| { n += 1; u4lo::from_lo(n) });
+        assert_eq!(bytes, [0x12, 0x34, 0x50]);
+    }
+
+    #[test]
+    fn reverse() {
+        let mut bytes = [0x01, 0x23, 0x45];
+        NibSliceFull::from_mut_slice(pairs_mut(&mut bytes)).reverse();
+        assert_eq!(bytes, [0x54, 0x32, 0x10]);
+        NibSliceNoL::from_mut_slice(pairs_mut(&mut bytes)).reverse();
+        assert_eq!(bytes, [0x50, 0x12, 0x34]);
+        NibSliceNoR::from_mut_slice(pairs_mut(&mut bytes)).reverse();
+        assert_eq!(bytes, [0x32, 0x10, 0x54]);
+        NibSliceNoBoth::from_mut_slice(pairs_mut(&mut bytes)).reverse();
+        assert_eq!(bytes, [0x35, 0x01, 0x24]);
+    }
+
+    #[test]
+    fn rotate() {
+        for start in 0..2 {
+            for end in 10..12 {
+                for mid in 0..(end - start) {
+                    let mut bytes = [0x01, 0x23, 0x45, 0x67, 0x89, 0xAB];
+                    let mut expected = nibs(NibSliceFull::from_slice(pairs(&bytes)));
+                    expected[start..end].rotate_left(mid);
+                    NibSliceFull::from_mut_slice(pairs_mut(&mut bytes))
+                        .slice_mut(start..end)
+                        .rotate_left(mid);
+                    assert_eq!(nibs(NibSliceFull::from_slice(pairs(&bytes))), expected);
+                    NibSliceFull::from_mut_slice(pairs_mut(&mut bytes))
+                        .slice_mut(start..end)
+                        .rotate_right(mid);
+                    expected[start..end].rotate_right(mid);
+                    assert_eq!(nibs(NibSliceFull::from_slice(pairs(&bytes))), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sort() {
+        let mut bytes = [0x3F, 0x19, 0x27, 0x03];
+        {
+            let mut slice = NibSliceFull::from_mut_slice(pairs_mut(&mut bytes)).slice_mut(1..7);
+            assert!(!slice.is_sorted());
+            slice.sort();
+            assert!(slice.is_sorted());
+            assert_eq!(slice.binary_search(u4lo::from_lo(7)), Ok(3));
+            assert_eq!(slice.binary_search(u4lo::from_lo(8)), Err(4));
+            assert_eq!(slice.binary_search(u4lo::from_lo(3)), Err(3));
+        }
+        assert_eq!(bytes, [0x30, 0x12, 0x79, 0xF3]);
+        NibSliceNoL::from_mut_slice(pairs_mut(&mut bytes)).sort_by_key(|nib| nib.to_lo() & 1);
+        assert_eq!(bytes, [0x30, 0x21, 0x79, 0xF3]);
+    }
+
+    #[test]
+    fn position_of() {
+        let bytes: Vec<u8> = (0..20).map(|i| if i == 13 { 0x5A } else { 0x11 }).collect();
+        let full = NibSliceFull::from_slice(pairs(&bytes));
+        assert_eq!(full.position_of(u4lo::from_lo(5)), Some(26));
+        assert_eq!(full.position_of(u4lo::from_lo(0xA)), Some(27));
+        assert_eq!(full.rposition_of(u4lo::from_lo(5)), Some(26));
+        assert_eq!(full.rposition_of(u4lo::from_lo(1)), Some(39));
+        assert_eq!(full.slice(3..).position_of(u4lo::from_lo(0xA)), Some(24));
+        assert_eq!(full.slice(..27).rposition_of(u4lo::from_lo(0xA)), None);
+        assert_eq!(full.position_of(u4lo::from_lo(0)), None);
+    }
+
+    #[test]
+    fn find() {
+        let bytes = [0x12, 0x31, 0x23, 0x12, 0x30];
+        let haystack = NibSliceNoR::from_slice(pairs(&bytes));
+        let needle_bytes = [0x01, 0x23];
+        let needle = NibSliceNoL::from_slice(pairs(&needle_bytes));
+        assert_eq!(haystack.find(needle), Some(0));
+        assert_eq!(haystack.rfind(needle), Some(6));
+        assert!(haystack.contains(needle));
+        assert!(haystack.starts_with(needle));
+        assert!(haystack.ends_with(needle));
+        assert!(!haystack.slice(1..).starts_with(needle));
+        assert!(!haystack.slice(4..8).contains(needle));
+
+        let indices: Vec<usize> = haystack.match_indices(needle).map(|(i, _)| i).collect();
+        assert_eq!(indices, [0, 3, 6]);
+        let sep = NibSliceNoBoth::from_slice(pairs(&needle_bytes));
+        let parts: Vec<_> = haystack.split(sep).map(|part| nibs(&part)).collect();
+        assert_eq!(parts, [vec![], vec![3], vec![3], vec![3]]);
+    }
+
+    #[test]
+    fn slice_ref() {
+        let mut bytes = [0x01, 0x23, 0x45];
+        let no_l = NibSliceNoL::from_slice(pairs(&bytes));
+        let r = NibSliceRef::from(no_l);
+        assert!(r.start_odd() && !r.end_odd());
+        assert_eq!(nibs(&r), [1, 2, 3, 4, 5]);
+        assert_eq!(r, *no_l);
+        match NibSlice::from(NibSliceRef::from(no_l.slice(1..3))) {
+            NibSlice::Full(s) => assert_eq!(nibs(s), [2, 3]),
+            _ => panic!("expected full"),
+        }
+
+        {
+            let mut r = NibSliceRefMut::from(NibSliceNoBoth::from_mut_slice(pairs_mut(&mut bytes)));
+            r.reverse();
+            assert_eq!(nibs(&r.as_shared()), [4, 3, 2, 1]);
+        }
+        assert_eq!(bytes, [0x04, 0x32, 0x15]);
+    }
+
+    #[test]
+    fn realign() {
+        let bytes: Vec<u8> = (0..40u8).map(|i| i.wrapping_mul(29)).collect();
+        let full = NibSliceFull::from_slice(pairs(&bytes));
+        for start in 0..3 {
+            for end in (start..40).chain(70..80) {
+                let slice = full.slice(start..end);
+                let expected: Vec<u8> = nibs(&slice)
+                    .chunks(2)
+                    .map(|c| c[0] << 4 | c.get(1).cloned().unwrap_or(0))
+                    .collect();
+                let mut iter = slice.realigned_bytes();
+                assert_eq!(iter.len(), expected.len());
+                iter.next();
+                assert_eq!(iter.len(), expected.len().saturating_sub(1));
+                assert_eq!(slice.realigned_bytes().collect::<Vec<u8>>(), expected);
+                assert_eq!(slice.to_aligned_bytes(), expected);
+                let vec = slice.to_vec();
+                assert!(vec.is_aligned());
+                assert_eq!(nibs(&vec), nibs(&slice));
+            }
+        }
+    }
+
+    #[test]
+    fn slice_mut() {
+        let mut bytes = [0x01, 0x23, 0x45];
+        {
+            let full = NibSliceFull::from_mut_slice(pairs_mut(&mut bytes));
+            let mut sub = full.slice_mut(1..4);
+            for cell in sub.nibbles_mut() {
+                cell.set_from_lo(u4lo::from_lo(0xF));
+            }
+        }
+        assert_eq!(bytes, [0x0F, 0xFF, 0x45]);
+    }
+
+    #[test]
+    fn cells() {
+        let bytes = [Cell::new(0x01), Cell::new(0x23), Cell::new(0x45)];
+        let cells = NibSliceCell::from_byte_cells(&bytes);
+        let (left, right) = cells.slice(1..).split_at(2);
+        left.set(1, u4lo::from_lo(0xA));
+        right.swap(0, 2);
+        assert_eq!(left.replace(0, u4lo::from_lo(0xB)).to_lo(), 1);
+        assert_eq!(bytes.iter().map(Cell::get).collect::<Vec<_>>(), [0x0B, 0xA5, 0x43]);
+        assert_eq!(nibs(&cells.to_vec()), [0, 0xB, 0xA, 5, 4, 3]);
+
+        let mut pairs = [u4x2::from_byte(0x12)];
+        let cell = Cell::from_mut(&mut pairs[..]);
+        let cells = NibSliceCell::from_cell(cell);
+        cells.swap(0, 1);
+        assert_eq!(cells.nibbles().map(|cell| cell.get_lo().to_lo()).collect::<Vec<_>>(), [2, 1]);
+
+        let bytes = [Cell::new(0x10), Cell::new(0x32)];
+        let cells = NibSliceCell::<LoFirst>::from_ordered_byte_cells(&bytes);
+        assert_eq!(nibs(&cells.to_vec()), [0, 1, 2, 3]);
+        cells.slice(1..).set(0, u4lo::from_lo(0xF));
+        cells.swap(2, 3);
+        assert_eq!(bytes.iter().map(Cell::get).collect::<Vec<_>>(), [0xF0, 0x23]);
+        let rev: Vec<u8> = cells.nibbles().rev().map(|cell| cell.get_lo().to_lo()).collect();
+        assert_eq!(rev, [2, 3, 0xF, 0]);
+    }
+}
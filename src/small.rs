@@ -0,0 +1,537 @@
+//! Types for small vectors of nibbles.
+use std::slice as stdslice;
+use std::iter::FromIterator;
+use std::ops::RangeBounds;
+use arrayvec::Array;
+use array::{NibArrayEven, NibArrayOdd, NibArrayVec};
+use base::{u4lo, u4};
+use pair::u4x2;
+use slice::{self, NibSlice, NibSliceAligned, NibSliceAlignedMut, NibSliceExt};
+use vec::{NibVec, PadPolicy};
+use common::{as_bytes, get_nib, nib_range};
+
+macro_rules! with_inner {
+    ($self:expr, $v:ident => $e:expr) => {
+        match $self.inner {
+            Storage::Inline(ref $v) => $e,
+            Storage::Heap(ref $v) => $e,
+        }
+    }
+}
+
+macro_rules! with_inner_mut {
+    ($self:expr, $v:ident => $e:expr) => {
+        match $self.inner {
+            Storage::Inline(ref mut $v) => $e,
+            Storage::Heap(ref mut $v) => $e,
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Storage<A: Array<Item = u4x2>> {
+    Inline(NibArrayVec<A>),
+    Heap(NibVec),
+}
+
+/// A vector of nibbles which stores up to `A` pairs inline before moving to the heap.
+#[derive(Clone)]
+pub struct SmallNibVec<A: Array<Item = u4x2>> {
+    inner: Storage<A>,
+}
+impl<A: Array<Item = u4x2>> SmallNibVec<A> {
+    /// Creates an empty `SmallNibVec`.
+    pub fn new() -> Self {
+        SmallNibVec { inner: Storage::Inline(NibArrayVec::new()) }
+    }
+
+    /// Creates an empty vector with space for at least `capacity` nibbles.
+    ///
+    /// The vector is only allocated on the heap if the capacity doesn't fit inline.
+    pub fn with_capacity(capacity: usize) -> Self {
+        if capacity <= A::capacity() << 1 {
+            Self::new()
+        } else {
+            SmallNibVec { inner: Storage::Heap(NibVec::with_capacity(capacity)) }
+        }
+    }
+
+    /// Creates a vector from the first `nib_len` nibbles in a vector of bytes.
+    ///
+    /// The nibbles are copied inline if they fit, and the bytes are reused on the heap otherwise.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are fewer than `nib_len` nibbles in the bytes.
+    pub fn from_bytes_with_len(bytes: Vec<u8>, nib_len: usize) -> Self {
+        if nib_len <= A::capacity() << 1 {
+            assert!(
+                nib_len <= bytes.len() << 1,
+                "length {} out of range for {} bytes", nib_len, bytes.len()
+            );
+            let inline = NibArrayVec::from_nibs(&NibSlice::from_bytes(&bytes, ..nib_len));
+            SmallNibVec { inner: Storage::Inline(inline) }
+        } else {
+            SmallNibVec { inner: Storage::Heap(NibVec::from_bytes_with_len(bytes, nib_len)) }
+        }
+    }
+
+    /// Converts the vector into bytes, using the given policy to fill out an odd final byte.
+    ///
+    /// Fails only if the vector has an odd length and the policy is `PadPolicy::Error`.
+    pub fn into_bytes(self, pad: PadPolicy) -> Result<Vec<u8>, Self> {
+        self.into_vec().into_bytes(pad).map_err(Self::from)
+    }
+
+    /// Views the vector as bytes, if it has an even length.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        if self.len() & 1 == 0 {
+            Some(as_bytes(with_inner!(self, v => v.nibble_pairs().as_slice())))
+        } else {
+            None
+        }
+    }
+
+    /// Converts the vector into a `NibVec`, moving it to the heap if it isn't already.
+    pub fn into_vec(self) -> NibVec {
+        match self.inner {
+            Storage::Inline(v) => v.to_vec(),
+            Storage::Heap(v) => v,
+        }
+    }
+
+    /// Whether the vector has moved to the heap.
+    pub fn spilled(&self) -> bool {
+        match self.inner {
+            Storage::Inline(_) => false,
+            Storage::Heap(_) => true,
+        }
+    }
+
+    /// Number of nibbles in the vector.
+    pub fn len(&self) -> usize {
+        with_inner!(self, v => v.len())
+    }
+
+    /// Whether the vector is empty.
+    pub fn is_empty(&self) -> bool {
+        with_inner!(self, v => v.is_empty())
+    }
+
+    /// How many nibbles can be stored in the vector without reallocating.
+    pub fn capacity(&self) -> usize {
+        with_inner!(self, v => v.capacity())
+    }
+
+    /// Moves the vector to the heap if it can't hold `len` nibbles inline.
+    fn grow_to(&mut self, len: usize) {
+        let heap = match self.inner {
+            Storage::Inline(ref v) if len > v.capacity() => {
+                let mut heap = NibVec::with_capacity(len);
+                heap.extend_from_nibs(v);
+                heap
+            }
+            _ => return,
+        };
+        self.inner = Storage::Heap(heap);
+    }
+
+    /// Reserves space for at least `additional` more nibbles.
+    pub fn reserve(&mut self, additional: usize) {
+        let len = self.len();
+        self.grow_to(len + additional);
+        if let Storage::Heap(ref mut v) = self.inner {
+            v.reserve(additional);
+        }
+    }
+
+    /// Shrinks the capacity of the vector as much as possible, moving it back inline if it fits.
+    pub fn shrink_to_fit(&mut self) {
+        let inline = match self.inner {
+            Storage::Heap(ref mut v) if v.len() <= A::capacity() << 1 => {
+                let mut inline = NibArrayVec::new();
+                inline.extend_from_nibs(v);
+                inline
+            }
+            Storage::Heap(ref mut v) => return v.shrink_to_fit(),
+            Storage::Inline(_) => return,
+        };
+        self.inner = Storage::Inline(inline);
+    }
+
+    /// Shortens the vector to the given length.
+    ///
+    /// Does nothing if the vector is already shorter than that.
+    pub fn truncate(&mut self, len: usize) {
+        with_inner_mut!(self, v => v.truncate(len))
+    }
+
+    /// Resizes the vector to the given length, filling any new space with the given nibble.
+    pub fn resize<T: u4>(&mut self, len: usize, nib: T) {
+        self.grow_to(len);
+        with_inner_mut!(self, v => v.resize(len, nib.to_u4lo()))
+    }
+
+    /// Splits the vector in two at the given index, returning the nibbles after it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at > len`.
+    pub fn split_off(&mut self, at: usize) -> Self {
+        let len = self.len();
+        assert!(at <= len, "split index {} out of range for vector of length {}", at, len);
+        let mut tail = Self::with_capacity(len - at);
+        tail.extend_from_nibs(&self.slice(at..));
+        self.truncate(at);
+        tail
+    }
+
+    /// Moves all the nibbles from `other` onto the end of this vector, leaving `other` empty.
+    pub fn append(&mut self, other: &mut Self) {
+        self.extend_from_nibs(other);
+        other.clear();
+    }
+
+    /// Copies all the nibbles from a slice onto the end of this vector.
+    ///
+    /// The slice may have any alignment.
+    pub fn extend_from_nibs<S: ?Sized + NibSliceExt>(&mut self, nibs: &S) {
+        let len = self.len();
+        self.grow_to(len + nibs.len());
+        with_inner_mut!(self, v => v.extend_from_nibs(nibs))
+    }
+
+    /// Inserts all the nibbles from a slice at the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    pub fn insert_nibs<S: ?Sized + NibSliceExt>(&mut self, index: usize, nibs: &S) {
+        let len = self.len();
+        self.grow_to(len + nibs.len());
+        with_inner_mut!(self, v => v.insert_nibs(index, nibs))
+    }
+
+    /// Removes the given range of nibbles from the vector.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn remove_range<R: RangeBounds<usize>>(&mut self, range: R) {
+        with_inner_mut!(self, v => v.remove_range(range))
+    }
+
+    /// Removes the given range of nibbles from the vector, returning them as an iterator.
+    ///
+    /// The nibbles are removed when the iterator is dropped, even if it isn't fully consumed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn drain<R: RangeBounds<usize>>(&mut self, range: R) -> Drain<A> {
+        let (start, end) = nib_range(range, self.len());
+        Drain { vec: self, start, end, front: start, back: end }
+    }
+
+    /// Replaces the given range of nibbles with the ones from the iterator, returning the removed
+    /// nibbles.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn splice<R, I, T>(&mut self, range: R, replace_with: I) -> Self
+    where
+        R: RangeBounds<usize>,
+        I: IntoIterator<Item = T>,
+        T: u4,
+    {
+        let len = self.len();
+        let (start, end) = nib_range(range, len);
+        let replacement: Self = replace_with.into_iter().collect();
+        self.grow_to(len - (end - start) + replacement.len());
+        let replacement = replacement.nibbles().map(|nib| nib.to_u4lo());
+        let inner = match self.inner {
+            Storage::Inline(ref mut v) => Storage::Inline(v.splice(start..end, replacement)),
+            Storage::Heap(ref mut v) => Storage::Heap(v.splice(start..end, replacement)),
+        };
+        SmallNibVec { inner }
+    }
+
+    /// Keeps only the nibbles for which the given predicate returns `true`.
+    pub fn retain<F: FnMut(u4lo) -> bool>(&mut self, f: F) {
+        with_inner_mut!(self, v => v.retain(f))
+    }
+
+    /// Pushes a nibble onto the vector.
+    pub fn push<T: u4>(&mut self, nib: T) {
+        let len = self.len();
+        self.grow_to(len + 1);
+        with_inner_mut!(self, v => v.push(nib))
+    }
+
+    /// Inserts a nibble into the vector at the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    pub fn insert<T: u4>(&mut self, index: usize, nib: T) {
+        let len = self.len();
+        self.grow_to(len + 1);
+        with_inner_mut!(self, v => v.insert(index, nib))
+    }
+
+    /// Removes a nibble from the vector at the given index, converting it to a high-order nibble.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= len`.
+    pub fn remove<T: u4>(&mut self, index: usize) -> T {
+        with_inner_mut!(self, v => v.remove(index))
+    }
+
+    /// Removes a nibble from the vector, converting it to a high-order nibble.
+    pub fn pop<T: u4>(&mut self) -> Option<T> {
+        with_inner_mut!(self, v => v.pop())
+    }
+
+    /// Removes consecutive repeated nibbles from the vector.
+    ///
+    /// If the vector is sorted, this removes all duplicates.
+    pub fn dedup(&mut self) {
+        with_inner_mut!(self, v => v.dedup())
+    }
+
+    /// Clears the vector, removing all nibbles.
+    pub fn clear(&mut self) {
+        with_inner_mut!(self, v => v.clear())
+    }
+
+    /// Converts the vector into an odd array, if it has exactly one less nibble than the array.
+    pub fn into_odd_array<B: Array<Item = u4x2>>(self) -> Result<NibArrayOdd<B>, Self> {
+        self.into_vec().into_odd_array().map_err(Self::from)
+    }
+
+    /// Converts the vector into an even array, if it has exactly as many nibbles as the array.
+    pub fn into_even_array<B: Array<Item = u4x2>>(self) -> Result<NibArrayEven<B>, Self> {
+        self.into_vec().into_even_array().map_err(Self::from)
+    }
+
+    /// Intreprets this vector as a slice.
+    pub fn as_slice(&self) -> NibSliceAligned {
+        with_inner!(self, v => v.as_slice())
+    }
+
+    /// Intreprets this vector as a mutable slice.
+    pub fn as_mut_slice(&mut self) -> NibSliceAlignedMut {
+        with_inner_mut!(self, v => v.as_mut_slice())
+    }
+}
+impl<A: Array<Item = u4x2>> Default for SmallNibVec<A> {
+    fn default() -> Self {
+        SmallNibVec::new()
+    }
+}
+impl<A: Array<Item = u4x2>> From<NibVec> for SmallNibVec<A> {
+    fn from(vec: NibVec) -> Self {
+        SmallNibVec { inner: Storage::Heap(vec) }
+    }
+}
+impl<A: Array<Item = u4x2>> From<NibArrayVec<A>> for SmallNibVec<A> {
+    fn from(vec: NibArrayVec<A>) -> Self {
+        SmallNibVec { inner: Storage::Inline(vec) }
+    }
+}
+impl<A: Array<Item = u4x2>, T: u4> FromIterator<T> for SmallNibVec<A> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut vec = Self::new();
+        vec.extend(iter);
+        vec
+    }
+}
+impl<'a, A: Array<Item = u4x2>> FromIterator<&'a u4> for SmallNibVec<A> {
+    fn from_iter<I: IntoIterator<Item = &'a u4>>(iter: I) -> Self {
+        let mut vec = Self::new();
+        vec.extend(iter);
+        vec
+    }
+}
+impl<A: Array<Item = u4x2>> FromIterator<u4x2> for SmallNibVec<A> {
+    fn from_iter<I: IntoIterator<Item = u4x2>>(iter: I) -> Self {
+        let mut vec = Self::new();
+        vec.extend(iter);
+        vec
+    }
+}
+impl<A: Array<Item = u4x2>, T: u4> Extend<T> for SmallNibVec<A> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for nib in iter {
+            self.push(nib);
+        }
+    }
+}
+impl<'a, A: Array<Item = u4x2>> Extend<&'a u4> for SmallNibVec<A> {
+    fn extend<I: IntoIterator<Item = &'a u4>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for nib in iter {
+            self.push(nib.to_u4lo());
+        }
+    }
+}
+impl<A: Array<Item = u4x2>> Extend<u4x2> for SmallNibVec<A> {
+    fn extend<I: IntoIterator<Item = u4x2>>(&mut self, iter: I) {
+        for pair in iter {
+            self.push(*pair.hi());
+            self.push(*pair.lo());
+        }
+    }
+}
+
+/// A draining iterator for `SmallNibVec`.
+///
+/// Created by [`SmallNibVec::drain`](struct.SmallNibVec.html#method.drain).
+#[derive(Debug)]
+pub struct Drain<'a, A: 'a + Array<Item = u4x2>> {
+    vec: &'a mut SmallNibVec<A>,
+    start: usize,
+    end: usize,
+    front: usize,
+    back: usize,
+}
+impl<'a, A: Array<Item = u4x2>> Drain<'a, A> {
+    fn get(&self, idx: usize) -> u4lo {
+        get_nib(with_inner!(self.vec, v => v.nibble_pairs().as_slice()), idx)
+    }
+}
+impl<'a, A: Array<Item = u4x2>> Iterator for Drain<'a, A> {
+    type Item = u4lo;
+    fn next(&mut self) -> Option<u4lo> {
+        if self.front < self.back {
+            self.front += 1;
+            Some(self.get(self.front - 1))
+        } else {
+            None
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.back - self.front;
+        (len, Some(len))
+    }
+}
+impl<'a, A: Array<Item = u4x2>> DoubleEndedIterator for Drain<'a, A> {
+    fn next_back(&mut self) -> Option<u4lo> {
+        if self.front < self.back {
+            self.back -= 1;
+            Some(self.get(self.back))
+        } else {
+            None
+        }
+    }
+}
+impl<'a, A: Array<Item = u4x2>> ExactSizeIterator for Drain<'a, A> {}
+impl<'a, A: Array<Item = u4x2>> Drop for Drain<'a, A> {
+    fn drop(&mut self) {
+        self.vec.remove_range(self.start..self.end);
+    }
+}
+
+impl<A: Array<Item = u4x2>> slice::private::Sealed for SmallNibVec<A> {
+    #[inline(always)]
+    fn has_left_hi(&self) -> bool { true }
+    #[inline(always)]
+    fn has_right_lo(&self) -> bool { self.as_slice().has_right_lo() }
+    #[inline(always)]
+    fn iter(&self) -> stdslice::Iter<u4x2> { with_inner!(self, v => slice::private::Sealed::iter(v)) }
+}
+impl<A: Array<Item = u4x2>> slice::private::SealedMut for SmallNibVec<A> {
+    #[inline(always)]
+    fn iter_mut(&mut self) -> stdslice::IterMut<u4x2> {
+        with_inner_mut!(self, v => slice::private::SealedMut::iter_mut(v))
+    }
+}
+impl<A: Array<Item = u4x2>> slice::NibSliceExt for SmallNibVec<A> {}
+impl<A: Array<Item = u4x2>> slice::NibSliceMutExt for SmallNibVec<A> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::XorShift;
+
+    type Small = SmallNibVec<[u4x2; 4]>;
+
+    fn nibs(vec: &Small) -> Vec<u8> {
+        vec.nibbles().map(|nib| nib.to_lo()).collect()
+    }
+
+    #[test]
+    fn spill() {
+        let mut vec = Small::new();
+        for i in 0..8 {
+            vec.push(u4lo::from_lo(i));
+        }
+        assert!(!vec.spilled());
+        assert_eq!(vec.capacity(), 8);
+        vec.insert(3, u4lo::from_lo(0xF));
+        assert!(vec.spilled());
+        assert_eq!(nibs(&vec), [0, 1, 2, 0xF, 3, 4, 5, 6, 7]);
+        vec.truncate(7);
+        vec.shrink_to_fit();
+        assert!(!vec.spilled());
+        assert_eq!(nibs(&vec), [0, 1, 2, 0xF, 3, 4, 5]);
+        assert_eq!(vec, NibVec::from_str("012f345").unwrap());
+    }
+
+    #[test]
+    fn bytes() {
+        let vec = Small::from_bytes_with_len(vec![0x12, 0x34, 0x56], 5);
+        assert!(!vec.spilled());
+        assert_eq!(nibs(&vec), [1, 2, 3, 4, 5]);
+        let vec = Small::from_bytes_with_len(vec![0x12, 0x34, 0x56, 0x78, 0x9A], 9);
+        assert!(vec.spilled());
+        assert_eq!(nibs(&vec), [1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(vec.into_bytes(PadPolicy::Zero).unwrap(), [0x12, 0x34, 0x56, 0x78, 0x90]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn bytes_too_short() {
+        Small::from_bytes_with_len(vec![0x12], 3);
+    }
+
+    #[test]
+    fn model() {
+        let mut rng = XorShift(0x5851_f42d_4c95_7f2d);
+        for _ in 0..20 {
+            let mut vec = Small::new();
+            let mut model = Vec::new();
+            for _ in 0..100 {
+                let len = model.len();
+                match rng.below(6) {
+                    0 | 1 => {
+                        let nib = rng.nib();
+                        vec.push(u4lo::from_lo(nib));
+                        model.push(nib);
+                    }
+                    2 => assert_eq!(vec.pop::<u4lo>().map(|nib| nib.to_lo()), model.pop()),
+                    3 => {
+                        let (start, end) = rng.range(len);
+                        let drained: Vec<u8> = vec.drain(start..end).map(|nib| nib.to_lo()).collect();
+                        assert_eq!(drained, model.drain(start..end).collect::<Vec<_>>());
+                    }
+                    4 => {
+                        let (start, end) = rng.range(len);
+                        let new: Vec<u8> = (0..rng.below(6)).map(|_| rng.nib()).collect();
+                        let removed = vec.splice(start..end, new.iter().map(|&nib| u4lo::from_lo(nib)));
+                        assert_eq!(nibs(&removed), model.splice(start..end, new).collect::<Vec<_>>());
+                    }
+                    _ => vec.shrink_to_fit(),
+                }
+                assert_eq!(nibs(&vec), model);
+                assert_eq!(vec.len(), model.len());
+                assert!(vec.spilled() || model.len() <= 8);
+            }
+        }
+    }
+}
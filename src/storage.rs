@@ -0,0 +1,213 @@
+//! Views of arbitrary byte storage as nibbles.
+use core::ops::{Range, RangeBounds};
+use core::marker::PhantomData;
+use core::slice;
+use base::u4;
+#[cfg(feature = "std")]
+use base::u4lo;
+use pair::u4x2;
+use slice::{private, NibSlice, NibSliceMut, NibSliceExt, NibSliceMutExt};
+use order::{HiFirst, NibOrder};
+use common::{as_pairs, as_pairs_mut, get_ordered_nib, nib_range, pair_range, set_ordered_nib};
+#[cfg(feature = "std")]
+use vec::NibVec;
+
+/// Storage which can be viewed as nibble pairs.
+///
+/// This is implemented for anything which can be borrowed as bytes, like `Vec<u8>`, `Box<[u8]>`,
+/// `&mut [u8]` or shared buffers.
+pub trait NibStorage {
+    /// Borrows the storage as nibble pairs.
+    fn as_pairs(&self) -> &[u4x2];
+}
+
+/// Storage which can be mutated as nibble pairs.
+pub trait NibStorageMut: NibStorage {
+    /// Mutably borrows the storage as nibble pairs.
+    fn as_pairs_mut(&mut self) -> &mut [u4x2];
+}
+
+impl<T: ?Sized + AsRef<[u8]>> NibStorage for T {
+    #[inline(always)]
+    fn as_pairs(&self) -> &[u4x2] {
+        as_pairs(self.as_ref())
+    }
+}
+impl<T: ?Sized + AsRef<[u8]> + AsMut<[u8]>> NibStorageMut for T {
+    #[inline(always)]
+    fn as_pairs_mut(&mut self) -> &mut [u4x2] {
+        as_pairs_mut(self.as_mut())
+    }
+}
+
+/// A range of nibbles inside some storage, without copying it.
+///
+/// The bytes are read high-order nibble first by default. Views in another order, created with
+/// `from_ordered`, can only be accessed through the methods on this type; the slice methods from
+/// `NibSliceExt` assume the default order.
+#[derive(Clone)]
+pub struct NibBytes<B: NibStorage, O: NibOrder = HiFirst> {
+    storage: B,
+    pairs: Range<usize>,
+    has_left_hi: bool,
+    has_right_lo: bool,
+    order: PhantomData<O>,
+}
+impl<B: NibStorage> NibBytes<B> {
+    /// Views all the nibbles in the storage.
+    pub fn new(storage: B) -> Self {
+        NibBytes::from_ordered(storage, ..)
+    }
+
+    /// Views the given range of nibbles in the storage.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn with_range<R: RangeBounds<usize>>(storage: B, range: R) -> Self {
+        NibBytes::from_ordered(storage, range)
+    }
+
+    /// Intreprets this view as a slice.
+    pub fn as_slice(&self) -> NibSlice {
+        NibSlice::from_parts(self.pairs(), self.has_left_hi, self.has_right_lo)
+    }
+}
+impl<B: NibStorageMut> NibBytes<B> {
+    /// Intreprets this view as a mutable slice.
+    pub fn as_mut_slice(&mut self) -> NibSliceMut {
+        let pairs = &mut self.storage.as_pairs_mut()[self.pairs.clone()];
+        NibSliceMut::from_parts(pairs, self.has_left_hi, self.has_right_lo)
+    }
+}
+impl<B: NibStorage, O: NibOrder> NibBytes<B, O> {
+    /// Views the given range of nibbles in storage whose bytes are packed in this view's order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn from_ordered<R: RangeBounds<usize>>(storage: B, range: R) -> Self {
+        let (start, end) = nib_range(range, storage.as_pairs().len() << 1);
+        let (pairs, has_left_hi, has_right_lo) = pair_range(start, end);
+        NibBytes { storage, pairs, has_left_hi, has_right_lo, order: PhantomData }
+    }
+
+    /// Gets a reference to the underlying storage.
+    pub fn get_ref(&self) -> &B {
+        &self.storage
+    }
+
+    /// Unwraps the underlying storage.
+    pub fn into_inner(self) -> B {
+        self.storage
+    }
+
+    fn pairs(&self) -> &[u4x2] {
+        &self.storage.as_pairs()[self.pairs.clone()]
+    }
+
+    /// Number of nibbles in the view.
+    pub fn len(&self) -> usize {
+        let hi = !self.has_left_hi as usize;
+        let lo = !self.has_right_lo as usize;
+        ((self.pairs.end - self.pairs.start) << 1).saturating_sub(hi + lo)
+    }
+
+    /// Whether the view is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Gets the nibble at the given index, if it's in bounds.
+    pub fn get_nib<T: u4>(&self, idx: usize) -> Option<T> {
+        if idx < self.len() {
+            Some(get_ordered_nib::<O, T>(self.pairs(), idx + !self.has_left_hi as usize))
+        } else {
+            None
+        }
+    }
+
+    /// Copies the nibbles in this view into a new, aligned `NibVec`.
+    #[cfg(feature = "std")]
+    pub fn to_vec(&self) -> NibVec {
+        if O::LO_FIRST {
+            (0..self.len())
+                .map(|idx| self.get_nib::<u4lo>(idx).unwrap_or_else(|| unreachable!()))
+                .collect()
+        } else {
+            NibSlice::from_parts(self.pairs(), self.has_left_hi, self.has_right_lo).to_vec()
+        }
+    }
+}
+impl<B: NibStorageMut, O: NibOrder> NibBytes<B, O> {
+    /// Sets the nibble at the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn set_nib<T: u4>(&mut self, idx: usize, nib: T) {
+        let len = self.len();
+        assert!(idx < len, "index {} out of range for slice of length {}", idx, len);
+        let idx = idx + !self.has_left_hi as usize;
+        set_ordered_nib::<O, T>(&mut self.storage.as_pairs_mut()[self.pairs.clone()], idx, nib);
+    }
+}
+impl<B: NibStorage> private::Sealed for NibBytes<B> {
+    #[inline(always)]
+    fn has_left_hi(&self) -> bool { self.has_left_hi }
+    #[inline(always)]
+    fn has_right_lo(&self) -> bool { self.has_right_lo }
+    #[inline(always)]
+    fn iter(&self) -> slice::Iter<u4x2> { self.pairs().iter() }
+}
+impl<B: NibStorageMut> private::SealedMut for NibBytes<B> {
+    #[inline(always)]
+    fn iter_mut(&mut self) -> slice::IterMut<u4x2> { self.storage.as_pairs_mut()[self.pairs.clone()].iter_mut() }
+}
+impl<B: NibStorage> NibSliceExt for NibBytes<B> {}
+impl<B: NibStorageMut> NibSliceMutExt for NibBytes<B> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base::u4lo;
+    use order::LoFirst;
+
+    #[test]
+    fn storage() {
+        let bytes = NibBytes::with_range(vec![0x12, 0x34, 0x56], 1..4);
+        assert_eq!(bytes.len(), 3);
+        assert_eq!(bytes.nibbles().map(|nib| nib.to_lo()).collect::<Vec<_>>(), [2, 3, 4]);
+        assert_eq!(bytes.as_slice(), NibSlice::from_bytes(&[0x12, 0x34], 1..));
+
+        let mut boxed = NibBytes::with_range(vec![0x12, 0x34].into_boxed_slice(), 1..3);
+        boxed.get_mut(1).unwrap().set_from_lo(u4lo::from_lo(0xf));
+        assert_eq!(&*boxed.into_inner(), [0x12, 0xf4]);
+
+        let mut buf = [0x12, 0x34];
+        {
+            let mut view = NibBytes::new(&mut buf[..]);
+            view.as_mut_slice().fill(u4lo::from_lo(0));
+            assert_eq!(view.len(), 4);
+        }
+        assert_eq!(buf, [0, 0]);
+        assert!(NibBytes::with_range(&[0x12][..], 1..1).is_empty());
+    }
+
+    #[test]
+    fn ordered() {
+        let mut bytes = NibBytes::<_, LoFirst>::from_ordered(vec![0x21, 0x43, 0x65], 1..4);
+        assert_eq!(bytes.len(), 3);
+        assert_eq!(bytes.get_nib::<u4lo>(0).map(|nib| nib.to_lo()), Some(2));
+        assert!(bytes.get_nib::<u4lo>(3).is_none());
+        assert_eq!(bytes.to_vec(), NibVec::from_str("234").unwrap());
+        bytes.set_nib(2, u4lo::from_lo(0xF));
+        assert_eq!(bytes.into_inner(), [0x21, 0xF3, 0x65]);
+
+        let mut bytes = NibBytes::with_range(vec![0x21, 0x43], 1..);
+        assert_eq!(bytes.get_nib::<u4lo>(0).map(|nib| nib.to_lo()), Some(1));
+        bytes.set_nib(2, u4lo::from_lo(0));
+        assert_eq!(bytes.to_vec(), NibVec::from_str("140").unwrap());
+        assert_eq!(format!("{:?}", NibBytes::<_, LoFirst>::from_ordered(&[0x21][..], ..)), "[1, 2]");
+    }
+}
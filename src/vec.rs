@@ -1,25 +1,54 @@
 //! Types for arrays of nibbles.
 use std::{slice as stdslice, mem};
+use std::borrow::Borrow;
+use std::marker::PhantomData;
+use std::iter::FromIterator;
+use std::ops::RangeBounds;
+use arrayvec::{Array, ArrayVec};
+use array::{NibArrayEven, NibArrayOdd, NibArrayVec};
 use base::{u4lo, u4};
 use pair::u4x2;
-use slice::{self, NibSliceAligned, NibSliceAlignedMut, NibSliceFull, NibSliceNoR};
-use common::{get_nib, set_nib, shift_left, shift_right};
+use slice::{self, NibKey, NibSliceAligned, NibSliceAlignedMut, NibSliceFull, NibSliceNoR};
+use order::{HiFirst, NibOrder};
+use common::{as_bytes, as_bytes_mut, copy_nibs, fill_nibs, get_nib, move_nibs, nib_range, set_nib, shift_left, shift_right};
+use slice::NibSliceExt;
+
+/// What to do with the unused half of the final byte when converting an odd-length vector to bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PadPolicy {
+    /// Fill the unused half of the last byte with zero.
+    Zero,
+
+    /// Fill the unused half of the last byte with `0xF`, as TBCD does.
+    F,
+
+    /// Add a leading zero nibble, so that the nibbles are right-aligned in the bytes and the last
+    /// nibble fills the second half of the last byte.
+    LeadingZero,
+
+    /// Refuse to convert odd-length vectors.
+    Error,
+}
 
 /// A `Vec` of nibbles.
+///
+/// The order parameter decides how nibbles are packed into bytes when converting to and from
+/// them; everything else works on the nibbles in order, whatever the packing.
 #[derive(Clone)]
-pub struct NibVec  {
+pub struct NibVec<O = HiFirst> {
     inner: Vec<u4x2>,
     has_right_lo: bool,
+    order: PhantomData<O>,
 }
 impl NibVec {
     /// Creates an empty `ArrayVec`.
     pub fn new() -> Self {
-        NibVec { inner: Vec::new(), has_right_lo: true }
+        NibVec::from_parts(Vec::new(), true)
     }
 
     /// Creates a vector from a vector of pairs.
     pub fn from_pair_vec(inner: Vec<u4x2>) -> Self {
-        NibVec { inner, has_right_lo: true }
+        NibVec::from_parts(inner, true)
     }
 
     /// Creates a vector from a vector of bytes.
@@ -27,43 +56,286 @@ impl NibVec {
         Self::from_pair_vec(unsafe { mem::transmute(inner) })
     }
 
+    /// Creates a vector from the first `nib_len` nibbles in a vector of bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are fewer than `nib_len` nibbles in the bytes.
+    pub fn from_bytes_with_len(bytes: Vec<u8>, nib_len: usize) -> Self {
+        assert!(
+            nib_len <= bytes.len() << 1,
+            "length {} out of range for {} bytes", nib_len, bytes.len()
+        );
+        let mut vec = Self::from_byte_vec(bytes);
+        vec.truncate(nib_len);
+        vec
+    }
+
+    /// Views the vector as bytes, if it has an even length.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        if self.has_right_lo {
+            Some(as_bytes(&self.inner))
+        } else {
+            None
+        }
+    }
+
+    /// Creates an empty vector with space for at least `capacity` nibbles.
+    pub fn with_capacity(capacity: usize) -> Self {
+        NibVec::from_parts(Vec::with_capacity((capacity + 1) >> 1), true)
+    }
+}
+impl<O: NibOrder> NibVec<O> {
+    pub(crate) fn from_parts(inner: Vec<u4x2>, has_right_lo: bool) -> Self {
+        NibVec { inner, has_right_lo, order: PhantomData }
+    }
+
+    /// Creates a vector from the first `nib_len` nibbles in a vector of bytes, packed in this
+    /// vector's order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are fewer than `nib_len` nibbles in the bytes.
+    pub fn from_ordered_bytes(mut bytes: Vec<u8>, nib_len: usize) -> Self {
+        O::reorder(&mut bytes);
+        NibVec::from_bytes_with_len(bytes, nib_len).into_order()
+    }
+
+    /// Converts the vector to another order, keeping the same nibbles.
+    ///
+    /// This doesn't touch the nibbles at all; they're only reordered when converted into bytes.
+    pub fn into_order<P: NibOrder>(self) -> NibVec<P> {
+        NibVec::from_parts(self.inner, self.has_right_lo)
+    }
+
+    /// Converts the vector into bytes, using the given policy to fill out an odd final byte.
+    ///
+    /// The nibbles are packed in this vector's order.
+    ///
+    /// Fails only if the vector has an odd length and the policy is `PadPolicy::Error`.
+    pub fn into_bytes(mut self, pad: PadPolicy) -> Result<Vec<u8>, Self> {
+        if !self.has_right_lo {
+            match pad {
+                PadPolicy::Zero => (),
+                PadPolicy::F => {
+                    let i = self.inner.len() - 1;
+                    self.inner[i].set_lo(u4lo::from_lo(0xF));
+                }
+                PadPolicy::LeadingZero => {
+                    shift_right(&mut self.inner, 0);
+                }
+                PadPolicy::Error => return Err(self),
+            }
+        }
+        let mut bytes = self.into_byte_vec();
+        O::reorder(&mut bytes);
+        Ok(bytes)
+    }
+
+
+    pub(crate) fn into_parts(self) -> (Vec<u4x2>, bool) {
+        (self.inner, self.has_right_lo)
+    }
+
+    pub(crate) fn into_byte_vec(self) -> Vec<u8> {
+        unsafe { mem::transmute(self.inner) }
+    }
+
     /// Number of nibbles in the vector.
     pub fn len(&self) -> usize {
-        (self.inner.len() >> 1).saturating_sub(!self.has_right_lo as usize)
+        (self.inner.len() << 1) - !self.has_right_lo as usize
     }
 
     /// Whether the vector is empty.
     pub fn is_empty(&self) -> bool {
-        self.inner.is_empty()
+        self.len() == 0
     }
 
     /// How many nibbles can be stored in the vector.
     pub fn capacity(&self) -> usize {
-        self.inner.capacity() >> 1
+        self.inner.capacity() << 1
     }
 
-    /// Pushes a nibble onto the vector.
+    /// Resizes the vector to the given length, leaving any new nibbles zeroed.
+    fn set_len_zeroed(&mut self, len: usize) {
+        let old_len = self.len();
+        self.inner.resize((len + 1) >> 1, u4x2::from_byte(0));
+        self.has_right_lo = len & 1 == 0;
+        if len & 1 == 1 {
+            let i = self.inner.len() - 1;
+            self.inner[i].set_lo(u4lo::from_lo(0));
+        }
+        if old_len & 1 == 1 && len > old_len {
+            set_nib(&mut self.inner, old_len, u4lo::from_lo(0));
+        }
+    }
+
+    /// Reserves space for at least `additional` more nibbles.
+    pub fn reserve(&mut self, additional: usize) {
+        let pairs = (self.len() + additional + 1) >> 1;
+        let len = self.inner.len();
+        self.inner.reserve(pairs - len);
+    }
+
+    /// Shrinks the capacity of the vector as much as possible.
+    pub fn shrink_to_fit(&mut self) {
+        self.inner.shrink_to_fit();
+    }
+
+    /// Shortens the vector to the given length.
+    ///
+    /// Does nothing if the vector is already shorter than that.
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len() {
+            self.set_len_zeroed(len);
+        }
+    }
+
+    /// Resizes the vector to the given length, filling any new space with the given nibble.
+    pub fn resize<T: u4>(&mut self, len: usize, nib: T) {
+        let old_len = self.len();
+        self.set_len_zeroed(len);
+        if len > old_len {
+            fill_nibs(&mut self.inner, old_len, len - old_len, nib.to_u4lo());
+        }
+    }
+
+    /// Splits the vector in two at the given index, returning the nibbles after it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at > len`.
+    pub fn split_off(&mut self, at: usize) -> Self {
+        let len = self.len();
+        assert!(at <= len, "split index {} out of range for vector of length {}", at, len);
+        let tail = self.slice(at..).to_vec().into_order();
+        self.truncate(at);
+        tail
+    }
+
+    /// Moves all the nibbles from `other` onto the end of this vector, leaving `other` empty.
+    pub fn append<P: NibOrder>(&mut self, other: &mut NibVec<P>) {
+        self.extend_from_nibs(other);
+        other.clear();
+    }
+
+    /// Copies all the nibbles from a slice onto the end of this vector.
+    ///
+    /// The slice may have any alignment.
+    pub fn extend_from_nibs<S: ?Sized + NibSliceExt>(&mut self, nibs: &S) {
+        let len = self.len();
+        let extra = nibs.len();
+        let start = !nibs.is_aligned() as usize;
+        self.set_len_zeroed(len + extra);
+        copy_nibs(&mut self.inner, len, nibs.nibble_pairs().as_slice(), start, extra);
+    }
+
+    /// Replaces the nibbles in `start..end` with the given ones, shifting the tail once.
+    fn replace_nibs<S: ?Sized + NibSliceExt>(&mut self, start: usize, end: usize, nibs: &S) {
+        let len = self.len();
+        let count = nibs.len();
+        let new_len = len - (end - start) + count;
+        if new_len > len {
+            self.set_len_zeroed(new_len);
+        }
+        move_nibs(&mut self.inner, end, start + count, len - end);
+        copy_nibs(&mut self.inner, start, nibs.nibble_pairs().as_slice(), !nibs.is_aligned() as usize, count);
+        self.truncate(new_len);
+    }
+
+    /// Inserts all the nibbles from a slice at the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    pub fn insert_nibs<S: ?Sized + NibSliceExt>(&mut self, index: usize, nibs: &S) {
+        let len = self.len();
+        assert!(index <= len, "insertion index {} out of range for vector of length {}", index, len);
+        self.replace_nibs(index, index, nibs);
+    }
+
+    /// Removes the given range of nibbles from the vector.
     ///
     /// # Panics
     ///
-    /// Panics if the vector is full.
+    /// Panics if the range is out of bounds.
+    pub fn remove_range<R: RangeBounds<usize>>(&mut self, range: R) {
+        let len = self.len();
+        let (start, end) = nib_range(range, len);
+        move_nibs(&mut self.inner, end, start, len - end);
+        self.truncate(len - (end - start));
+    }
+
+    /// Removes the given range of nibbles from the vector, returning them as an iterator.
+    ///
+    /// The nibbles are removed when the iterator is dropped, even if it isn't fully consumed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn drain<R: RangeBounds<usize>>(&mut self, range: R) -> Drain<O> {
+        let (start, end) = nib_range(range, self.len());
+        Drain { vec: self, start, end, front: start, back: end }
+    }
+
+    /// Replaces the given range of nibbles with the ones from the iterator, returning the removed
+    /// nibbles.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn splice<R, I, T>(&mut self, range: R, replace_with: I) -> Self
+    where
+        R: RangeBounds<usize>,
+        I: IntoIterator<Item = T>,
+        T: u4,
+    {
+        let (start, end) = nib_range(range, self.len());
+        let removed = self.slice(start..end).to_vec().into_order();
+        let replacement: NibVec = replace_with.into_iter().collect();
+        self.replace_nibs(start, end, &replacement);
+        removed
+    }
+
+    /// Keeps only the nibbles for which the given predicate returns `true`.
+    pub fn retain<F: FnMut(u4lo) -> bool>(&mut self, mut f: F) {
+        let len = self.len();
+        let mut write = 0;
+        for read in 0..len {
+            let nib: u4lo = get_nib(&self.inner, read);
+            if f(nib) {
+                set_nib(&mut self.inner, write, nib);
+                write += 1;
+            }
+        }
+        self.truncate(write);
+    }
+
+    /// Pushes a nibble onto the vector.
     pub fn push<T: u4>(&mut self, nib: T) {
-        self.has_right_lo = !self.has_right_lo;
         if self.has_right_lo {
             self.inner.push(u4x2::from_hi(nib.to_u4hi()));
         } else {
             let i = self.inner.len() - 1;
             self.inner[i].set_lo(nib);
         }
+        self.has_right_lo = !self.has_right_lo;
     }
 
     /// Inserts a nibble into the vector at the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
     pub fn insert<T: u4>(&mut self, index: usize, nib: T) {
+        let len = self.len();
+        assert!(index <= len, "insertion index {} out of range for vector of length {}", index, len);
         if self.has_right_lo {
-            self.push(u4lo::from_lo(0));
+            self.inner.push(u4x2::from_byte(0));
         }
         shift_right(self.inner.as_mut_slice(), index);
         set_nib(self.inner.as_mut_slice(), index, nib);
+        self.has_right_lo = !self.has_right_lo;
     }
 
     fn discard_at(&mut self, index: usize) {
@@ -75,7 +347,13 @@ impl NibVec {
     }
 
     /// Removes a nibble from the vector at the given index, converting it to a high-order nibble.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= len`.
     pub fn remove<T: u4>(&mut self, index: usize) -> T {
+        let len = self.len();
+        assert!(index < len, "removal index {} out of range for vector of length {}", index, len);
         let ret = get_nib(self.inner.as_slice(), index);
         self.discard_at(index);
         ret
@@ -83,12 +361,37 @@ impl NibVec {
 
     /// Removes a nibble from the vector, converting it to a high-order nibble.
     pub fn pop<T: u4>(&mut self) -> Option<T> {
-        self.has_right_lo = !self.has_right_lo;
         if self.has_right_lo {
-            Some(T::from_lo(self.inner[self.inner.len() - 1].lo().to_lo()))
+            let i = self.inner.len().checked_sub(1)?;
+            let ret = T::from_lo(self.inner[i].lo().to_lo());
+            self.inner[i].set_lo(u4lo::from_lo(0));
+            self.has_right_lo = false;
+            Some(ret)
         } else {
-            self.inner.pop().map(|pair| T::from_hi(pair.hi().to_hi()))
+            let pair = self.inner.pop()?;
+            self.has_right_lo = true;
+            Some(T::from_hi(pair.hi().to_hi()))
+        }
+    }
+
+    /// Removes consecutive repeated nibbles from the vector.
+    ///
+    /// If the vector is sorted, this removes all duplicates.
+    pub fn dedup(&mut self) {
+        let len = self.len();
+        if len == 0 {
+            return;
+        }
+        let mut write = 1;
+        for read in 1..len {
+            let nib: u4lo = get_nib(&self.inner, read);
+            let prev: u4lo = get_nib(&self.inner, write - 1);
+            if nib != prev {
+                set_nib(&mut self.inner, write, nib);
+                write += 1;
+            }
         }
+        self.truncate(write);
     }
 
     /// Clears the vector, removing all nibbles.
@@ -97,6 +400,26 @@ impl NibVec {
         self.has_right_lo = true;
     }
 
+    /// Converts the vector into an odd array, if it has exactly one less nibble than the array.
+    pub fn into_odd_array<A: Array<Item = u4x2>>(self) -> Result<NibArrayOdd<A>, Self> {
+        if !self.has_right_lo && self.inner.len() == A::capacity() {
+            let inner: ArrayVec<A> = self.inner.into_iter().collect();
+            Ok(NibArrayOdd::from(inner.into_inner().unwrap()))
+        } else {
+            Err(self)
+        }
+    }
+
+    /// Converts the vector into an even array, if it has exactly as many nibbles as the array.
+    pub fn into_even_array<A: Array<Item = u4x2>>(self) -> Result<NibArrayEven<A>, Self> {
+        if self.has_right_lo && self.inner.len() == A::capacity() {
+            let inner: ArrayVec<A> = self.inner.into_iter().collect();
+            Ok(NibArrayEven::from(inner.into_inner().unwrap()))
+        } else {
+            Err(self)
+        }
+    }
+
     /// Intreprets this array as a slice.
     pub fn as_slice(&self) -> NibSliceAligned {
         if self.has_right_lo {
@@ -115,12 +438,121 @@ impl NibVec {
         }
     }
 }
-impl Default for NibVec {
+impl<'a, O: 'static + NibOrder> Borrow<dyn NibKey + 'a> for NibVec<O> {
+    fn borrow(&self) -> &(dyn NibKey + 'a) {
+        self
+    }
+}
+impl<O: NibOrder> Default for NibVec<O> {
     fn default() -> Self {
-        NibVec::new()
+        NibVec::from_parts(Vec::new(), true)
+    }
+}
+impl<A: Array<Item = u4x2>> From<NibArrayVec<A>> for NibVec {
+    fn from(vec: NibArrayVec<A>) -> Self {
+        vec.to_vec()
+    }
+}
+impl<O: NibOrder, T: u4> FromIterator<T> for NibVec<O> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut vec = Self::default();
+        vec.extend(iter);
+        vec
+    }
+}
+impl<'a, O: NibOrder> FromIterator<&'a u4> for NibVec<O> {
+    fn from_iter<I: IntoIterator<Item = &'a u4>>(iter: I) -> Self {
+        let mut vec = Self::default();
+        vec.extend(iter);
+        vec
+    }
+}
+/// Collects pairs which are packed in this vector's order.
+impl<O: NibOrder> FromIterator<u4x2> for NibVec<O> {
+    fn from_iter<I: IntoIterator<Item = u4x2>>(iter: I) -> Self {
+        let mut vec = Self::default();
+        vec.extend(iter);
+        vec
+    }
+}
+impl<O: NibOrder, T: u4> Extend<T> for NibVec<O> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for nib in iter {
+            self.push(nib);
+        }
+    }
+}
+impl<'a, O: NibOrder> Extend<&'a u4> for NibVec<O> {
+    fn extend<I: IntoIterator<Item = &'a u4>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for nib in iter {
+            self.push(nib.to_u4lo());
+        }
+    }
+}
+/// Extends the vector with pairs which are packed in this vector's order.
+impl<O: NibOrder> Extend<u4x2> for NibVec<O> {
+    fn extend<I: IntoIterator<Item = u4x2>>(&mut self, iter: I) {
+        if self.has_right_lo {
+            let start = self.inner.len();
+            self.inner.extend(iter);
+            O::reorder(as_bytes_mut(&mut self.inner[start..]));
+        } else {
+            for mut pair in iter {
+                O::reorder(stdslice::from_mut(pair.byte_mut()));
+                self.push(*pair.hi());
+                self.push(*pair.lo());
+            }
+        }
+    }
+}
+/// A draining iterator for `NibVec`.
+///
+/// Created by [`NibVec::drain`](struct.NibVec.html#method.drain).
+#[derive(Debug)]
+pub struct Drain<'a, O: 'a + NibOrder = HiFirst> {
+    vec: &'a mut NibVec<O>,
+    start: usize,
+    end: usize,
+    front: usize,
+    back: usize,
+}
+impl<'a, O: NibOrder> Iterator for Drain<'a, O> {
+    type Item = u4lo;
+    fn next(&mut self) -> Option<u4lo> {
+        if self.front < self.back {
+            self.front += 1;
+            Some(get_nib(&self.vec.inner, self.front - 1))
+        } else {
+            None
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.back - self.front;
+        (len, Some(len))
+    }
+}
+impl<'a, O: NibOrder> DoubleEndedIterator for Drain<'a, O> {
+    fn next_back(&mut self) -> Option<u4lo> {
+        if self.front < self.back {
+            self.back -= 1;
+            Some(get_nib(&self.vec.inner, self.back))
+        } else {
+            None
+        }
+    }
+}
+impl<'a, O: NibOrder> ExactSizeIterator for Drain<'a, O> {}
+impl<'a, O: NibOrder> Drop for Drain<'a, O> {
+    fn drop(&mut self) {
+        self.vec.remove_range(self.start..self.end);
     }
 }
-impl slice::private::Sealed for NibVec {
+
+impl<O: NibOrder> slice::private::Sealed for NibVec<O> {
     #[inline(always)]
     fn has_left_hi(&self) -> bool { true }
     #[inline(always)]
@@ -128,9 +560,315 @@ impl slice::private::Sealed for NibVec {
     #[inline(always)]
     fn iter(&self) -> stdslice::Iter<u4x2> { self.inner.iter() }
 }
-impl slice::private::SealedMut for NibVec {
+impl<O: NibOrder> slice::private::SealedMut for NibVec<O> {
     #[inline(always)]
     fn iter_mut(&mut self) -> stdslice::IterMut<u4x2> { self.inner.iter_mut() }
 }
-impl slice::NibSliceExt for NibVec {}
-impl slice::NibSliceMutExt for NibVec {}
+impl<O: NibOrder> slice::NibSliceExt for NibVec<O> {}
+impl<O: NibOrder> slice::NibSliceMutExt for NibVec<O> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::XorShift;
+    use order::LoFirst;
+
+    fn nibs(vec: &NibVec) -> Vec<u8> {
+        vec.nibbles().map(u4::to_lo).collect()
+    }
+
+    fn nib_vec(nibs: &[u8]) -> NibVec {
+        nibs.iter().map(|&nib| u4lo::from_lo(nib)).collect()
+    }
+
+    #[test]
+    fn collect() {
+        let vec = nib_vec(&[1, 2, 3]);
+        assert_eq!(nibs(&vec), [1, 2, 3]);
+        let mut vec: NibVec = vec.nibbles().collect();
+        vec.extend(vec![u4x2::from_byte(0x45)]);
+        assert_eq!(nibs(&vec), [1, 2, 3, 4, 5]);
+        vec.extend(vec![u4x2::from_byte(0x67)]);
+        assert_eq!(nibs(&vec), [1, 2, 3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn push() {
+        let mut vec = NibVec::new();
+        for nib in 1..6 {
+            vec.push(u4lo::from_lo(nib));
+            assert_eq!(vec.len(), nib as usize);
+        }
+        assert_eq!(nibs(&vec), [1, 2, 3, 4, 5]);
+        assert_eq!(vec.as_bytes(), None);
+        vec.push(u4lo::from_lo(6));
+        assert_eq!(vec.as_bytes(), Some(&[0x12, 0x34, 0x56][..]));
+    }
+
+    #[test]
+    fn resize() {
+        let mut vec = NibVec::with_capacity(5);
+        assert!(vec.capacity() >= 5);
+        vec.resize(3, u4lo::from_lo(7));
+        assert_eq!(nibs(&vec), [7, 7, 7]);
+        vec.resize(6, u4lo::from_lo(1));
+        assert_eq!(nibs(&vec), [7, 7, 7, 1, 1, 1]);
+        vec.truncate(2);
+        assert_eq!(nibs(&vec), [7, 7]);
+        vec.truncate(4);
+        assert_eq!(nibs(&vec), [7, 7]);
+        vec.reserve(100);
+        assert!(vec.capacity() >= 102);
+        vec.shrink_to_fit();
+    }
+
+    #[test]
+    fn split_off_append() {
+        let mut vec = nib_vec(&[1, 2, 3, 4, 5]);
+        let mut tail = vec.split_off(3);
+        assert_eq!((nibs(&vec), nibs(&tail)), (vec![1, 2, 3], vec![4, 5]));
+        tail.append(&mut vec);
+        assert_eq!((nibs(&vec), nibs(&tail)), (vec![], vec![4, 5, 1, 2, 3]));
+        let other = nib_vec(&[6, 7, 8]);
+        tail.extend_from_nibs(&other.slice(1..));
+        assert_eq!(nibs(&tail), [4, 5, 1, 2, 3, 7, 8]);
+        tail.retain(|nib| nib.to_lo() & 1 == 1);
+        assert_eq!(nibs(&tail), [5, 1, 3, 7]);
+    }
+
+    #[test]
+    fn drain_splice() {
+        let mut vec = nib_vec(&[1, 2, 3, 4, 5, 6, 7]);
+        let drained: Vec<u8> = vec.drain(1..4).map(|nib| nib.to_lo()).collect();
+        assert_eq!((drained, nibs(&vec)), (vec![2, 3, 4], vec![1, 5, 6, 7]));
+        {
+            let mut drain = vec.drain(2..);
+            assert_eq!(drain.next_back().map(|nib| nib.to_lo()), Some(7));
+        }
+        assert_eq!(nibs(&vec), [1, 5]);
+
+        let removed = vec.splice(1..2, (8..13).map(u4lo::from_lo));
+        assert_eq!((nibs(&removed), nibs(&vec)), (vec![5], vec![1, 8, 9, 10, 11, 12]));
+        let removed = vec.splice(..4, Some(u4lo::from_lo(0)));
+        assert_eq!((nibs(&removed), nibs(&vec)), (vec![1, 8, 9, 10], vec![0, 11, 12]));
+    }
+
+    #[test]
+    fn insert_remove_range() {
+        let mut vec = nib_vec(&[1, 2, 3]);
+        let other = nib_vec(&[4, 5, 6, 7]);
+        vec.insert_nibs(1, &other);
+        assert_eq!(nibs(&vec), [1, 4, 5, 6, 7, 2, 3]);
+        vec.insert_nibs(7, &other.slice(1..2));
+        assert_eq!(nibs(&vec), [1, 4, 5, 6, 7, 2, 3, 5]);
+        vec.insert_nibs(0, &other.slice(1..4));
+        assert_eq!(nibs(&vec), [5, 6, 7, 1, 4, 5, 6, 7, 2, 3, 5]);
+        vec.remove_range(2..5);
+        assert_eq!(nibs(&vec), [5, 6, 5, 6, 7, 2, 3, 5]);
+        vec.remove_range(1..7);
+        assert_eq!(nibs(&vec), [5, 5]);
+        vec.remove_range(..);
+        assert_eq!(nibs(&vec), Vec::<u8>::new());
+
+        // long enough to exercise the pair-at-a-time paths in both directions
+        let long: Vec<u8> = (0..99u8).map(|i| i.wrapping_mul(7) & 0xF).collect();
+        for &(at, remove) in &[(0, 3), (1, 2), (2, 5), (3, 3), (50, 1)] {
+            let mut vec = nib_vec(&long);
+            vec.remove_range(at..at + remove);
+            let mut expected = long.clone();
+            expected.drain(at..at + remove);
+            assert_eq!(nibs(&vec), expected);
+            vec.insert_nibs(at, &nib_vec(&long[at..at + remove]));
+            assert_eq!(nibs(&vec), long);
+        }
+    }
+
+    #[test]
+    fn bytes() {
+        let vec = NibVec::from_bytes_with_len(vec![0x12, 0x34, 0x56], 5);
+        assert_eq!(nibs(&vec), [1, 2, 3, 4, 5]);
+        assert_eq!(vec.as_bytes(), None);
+        assert_eq!(vec.clone().into_bytes(PadPolicy::Zero).unwrap(), [0x12, 0x34, 0x50]);
+        assert_eq!(vec.clone().into_bytes(PadPolicy::F).unwrap(), [0x12, 0x34, 0x5F]);
+        assert_eq!(vec.clone().into_bytes(PadPolicy::LeadingZero).unwrap(), [0x01, 0x23, 0x45]);
+        assert!(vec.into_bytes(PadPolicy::Error).is_err());
+
+        let vec = NibVec::from_bytes_with_len(vec![0x12, 0x34, 0x56], 4);
+        assert_eq!(vec.as_bytes(), Some(&[0x12, 0x34][..]));
+        assert_eq!(vec.into_bytes(PadPolicy::Error).unwrap(), [0x12, 0x34]);
+    }
+
+    #[test]
+    fn order() {
+        let mut vec = NibVec::<LoFirst>::from_ordered_bytes(vec![0x21, 0xF3], 3);
+        assert_eq!(nibs(&vec.clone().into_order()), [1, 2, 3]);
+        assert_eq!(vec.clone().into_bytes(PadPolicy::F).unwrap(), [0x21, 0xF3]);
+        vec.insert(0, u4lo::from_lo(0));
+        vec.remove::<u4lo>(2);
+        vec.push(u4lo::from_lo(4));
+        assert_eq!(vec.clone().into_bytes(PadPolicy::Error).unwrap(), [0x10, 0x43]);
+        assert_eq!(vec.slice(1..).to_ordered_bytes::<LoFirst>(), [0x31, 0x04]);
+        assert_eq!(vec.into_order::<HiFirst>().into_bytes(PadPolicy::Error).unwrap(), [0x01, 0x34]);
+
+        let parsed: NibVec<LoFirst> = "123".parse().unwrap();
+        assert_eq!(parsed, NibVec::from_str("123").unwrap());
+        assert_eq!(parsed.into_bytes(PadPolicy::Zero).unwrap(), [0x21, 0x03]);
+    }
+
+    #[test]
+    fn order_pairs() {
+        let bytes = vec![0x21, 0x43, 0x65];
+        let vec: NibVec<LoFirst> = bytes.iter().map(|&b| u4x2::from_byte(b)).collect();
+        assert_eq!(nibs(&vec.clone().into_order()), [1, 2, 3, 4, 5, 6]);
+        assert_eq!(vec.into_bytes(PadPolicy::Error).unwrap(), bytes);
+
+        let mut vec = NibVec::<LoFirst>::from_ordered_bytes(vec![0x21, 0x03], 3);
+        vec.extend(vec![u4x2::from_byte(0x54), u4x2::from_byte(0x76)]);
+        assert_eq!(nibs(&vec.clone().into_order()), [1, 2, 3, 4, 5, 6, 7]);
+        vec.extend(vec![u4x2::from_byte(0x98)]);
+        assert_eq!(vec.into_bytes(PadPolicy::Zero).unwrap(), [0x21, 0x43, 0x65, 0x87, 0x09]);
+
+        let vec: NibVec = bytes.iter().map(|&b| u4x2::from_byte(b)).collect();
+        assert_eq!(vec.into_bytes(PadPolicy::Error).unwrap(), bytes);
+    }
+
+    fn check(vec: &NibVec, model: &[u8]) {
+        assert_eq!(nibs(vec), model);
+        assert_eq!(vec.len(), model.len());
+        assert_eq!(vec.is_empty(), model.is_empty());
+        assert_eq!(vec.nibbles().len(), model.len());
+        assert!(vec.capacity() >= vec.len());
+        assert_eq!(vec.inner.len(), (model.len() + 1) >> 1);
+        if model.len() & 1 == 1 {
+            assert_eq!(vec.inner[model.len() >> 1].lo().to_lo(), 0, "padding must stay zero");
+            assert_eq!(vec.as_bytes(), None);
+        } else {
+            assert_eq!(vec.as_bytes().map(<[u8]>::len), Some(model.len() >> 1));
+        }
+    }
+
+    #[test]
+    fn model() {
+        let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+        for _ in 0..20 {
+            let mut vec = NibVec::new();
+            let mut model = Vec::new();
+            for _ in 0..200 {
+                let len = model.len();
+                match rng.below(15) {
+                    0 | 1 => {
+                        let nib = rng.nib();
+                        vec.push(u4lo::from_lo(nib));
+                        model.push(nib);
+                    }
+                    2 => assert_eq!(vec.pop::<u4lo>().map(|nib| nib.to_lo()), model.pop()),
+                    3 => {
+                        let (idx, nib) = (rng.below(len + 1), rng.nib());
+                        vec.insert(idx, u4lo::from_lo(nib));
+                        model.insert(idx, nib);
+                    }
+                    4 => if len > 0 {
+                        let idx = rng.below(len);
+                        assert_eq!(vec.remove::<u4lo>(idx).to_lo(), model.remove(idx));
+                    },
+                    5 => {
+                        let new_len = rng.below(len + 2);
+                        vec.truncate(new_len);
+                        model.truncate(new_len);
+                    }
+                    6 => {
+                        let (new_len, nib) = (rng.below(len + 6), rng.nib());
+                        vec.resize(new_len, u4lo::from_lo(nib));
+                        model.resize(new_len, nib);
+                    }
+                    7 => {
+                        let (start, end) = rng.range(len);
+                        let drained: Vec<u8> = vec.drain(start..end).map(|nib| nib.to_lo()).collect();
+                        assert_eq!(drained, model.drain(start..end).collect::<Vec<_>>());
+                    }
+                    8 => {
+                        let (start, end) = rng.range(len);
+                        let new: Vec<u8> = (0..rng.below(6)).map(|_| rng.nib()).collect();
+                        let removed = vec.splice(start..end, new.iter().map(|&nib| u4lo::from_lo(nib)));
+                        assert_eq!(nibs(&removed), model.splice(start..end, new).collect::<Vec<_>>());
+                    }
+                    9 => {
+                        let other: Vec<u8> = (0..rng.below(9)).map(|_| rng.nib()).collect();
+                        let (start, end) = rng.range(other.len());
+                        let idx = rng.below(len + 1);
+                        vec.insert_nibs(idx, &nib_vec(&other).slice(start..end));
+                        for (i, &nib) in other[start..end].iter().enumerate() {
+                            model.insert(idx + i, nib);
+                        }
+                    }
+                    10 => {
+                        let (start, end) = rng.range(len);
+                        vec.remove_range(start..end);
+                        model.drain(start..end);
+                    }
+                    11 => {
+                        let other: Vec<u8> = (0..rng.below(9)).map(|_| rng.nib()).collect();
+                        let (start, end) = rng.range(other.len());
+                        vec.extend_from_nibs(&nib_vec(&other).slice(start..end));
+                        model.extend_from_slice(&other[start..end]);
+                    }
+                    12 => {
+                        let nib = rng.nib();
+                        vec.retain(|n| n.to_lo() != nib);
+                        model.retain(|&n| n != nib);
+                    }
+                    13 => {
+                        vec.dedup();
+                        model.dedup();
+                    }
+                    _ => {
+                        let at = rng.below(len + 1);
+                        let mut tail = vec.split_off(at);
+                        let model_tail = model.split_off(at);
+                        check(&vec, &model);
+                        check(&tail, &model_tail);
+                        vec.append(&mut tail);
+                        model.extend(model_tail);
+                        check(&tail, &[]);
+                    }
+                }
+                check(&vec, &model);
+            }
+        }
+    }
+
+    #[test]
+    fn into_array() {
+        let vec = nib_vec(&[1, 2, 3]);
+        let vec = vec.into_even_array::<[u4x2; 2]>().unwrap_err();
+        let odd = vec.into_odd_array::<[u4x2; 2]>().unwrap();
+        assert_eq!(nibs(&odd.to_vec()), [1, 2, 3]);
+        let vec = nib_vec(&[1, 2, 3, 4]);
+        assert!(vec.clone().into_odd_array::<[u4x2; 2]>().is_err());
+        assert!(vec.clone().into_even_array::<[u4x2; 3]>().is_err());
+        let even = vec.into_even_array::<[u4x2; 2]>().unwrap();
+        assert_eq!(nibs(&even.to_vec()), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn empty() {
+        let mut vec = NibVec::new();
+        assert_eq!(vec.pop::<u4lo>(), None);
+        assert!(vec.is_empty());
+        vec.push(u4lo::from_lo(1));
+        assert!(!vec.is_empty());
+        assert_eq!(vec.len(), 1);
+        assert_eq!(vec.pop::<u4lo>().map(|nib| nib.to_lo()), Some(1));
+        assert_eq!(vec.pop::<u4lo>(), None);
+    }
+
+    #[test]
+    fn dedup() {
+        let mut vec = NibVec::from_byte_vec(vec![0x11, 0x12, 0x22, 0x33]);
+        vec.dedup();
+        assert_eq!(nibs(&vec), [1, 2, 3]);
+        let mut vec = NibVec::from_byte_vec(vec![0x12, 0x23, 0x34]);
+        vec.dedup();
+        assert_eq!(nibs(&vec), [1, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,363 @@
+//! Nibble vectors backed by words instead of bytes.
+use std::cmp;
+use std::hash;
+use std::iter::FromIterator;
+use base::u4;
+use slice::{NibSliceExt, NibSlice};
+use vec::NibVec;
+use common::as_bytes;
+
+/// Number of nibbles in a word.
+const WORD_NIBS: usize = 16;
+
+/// Shift which moves the nibble at the given index within a word to the low-order position.
+#[inline(always)]
+fn nib_shift(idx: usize) -> usize {
+    (WORD_NIBS - 1 - idx % WORD_NIBS) << 2
+}
+
+/// A `Vec` of nibbles, stored sixteen to a `u64`.
+///
+/// Nibbles are stored from the most significant end of each word, so that the words of a vector
+/// read like the bytes of an aligned `NibVec`. Bulk operations like comparison, hashing, shifting
+/// and searching work a whole word at a time.
+#[derive(Clone, Default)]
+pub struct WideNibVec {
+    words: Vec<u64>,
+    len: usize,
+}
+impl WideNibVec {
+    /// Creates an empty `WideNibVec`.
+    pub fn new() -> Self {
+        WideNibVec { words: Vec::new(), len: 0 }
+    }
+
+    /// Creates an empty vector with space for at least `capacity` nibbles.
+    pub fn with_capacity(capacity: usize) -> Self {
+        WideNibVec { words: Vec::with_capacity((capacity + WORD_NIBS - 1) / WORD_NIBS), len: 0 }
+    }
+
+    /// Copies the nibbles in a slice into a new vector.
+    pub fn from_nibs<S: ?Sized + NibSliceExt>(nibs: &S) -> Self {
+        let vec = nibs.to_vec();
+        let len = vec.len();
+        let (pairs, _) = vec.into_parts();
+        let words = as_bytes(&pairs).chunks(8).map(|chunk| {
+            let mut bytes = [0; 8];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+            u64::from_be_bytes(bytes)
+        }).collect();
+        let mut wide = WideNibVec { words, len };
+        wide.clear_padding();
+        wide
+    }
+
+    /// Number of nibbles in the vector.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the vector is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// How many nibbles can be stored in the vector without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.words.capacity() * WORD_NIBS
+    }
+
+    /// Gets the words backing the vector.
+    ///
+    /// Nibbles past the end of the vector in the last word are always zero.
+    pub fn as_words(&self) -> &[u64] {
+        &self.words
+    }
+
+    /// Zeroes the nibbles in the last word which are past the end of the vector.
+    fn clear_padding(&mut self) {
+        let used = self.len % WORD_NIBS;
+        if used != 0 {
+            if let Some(last) = self.words.last_mut() {
+                *last &= !0 << ((WORD_NIBS - used) << 2);
+            }
+        }
+    }
+
+    /// Gets the word made of the sixteen nibbles starting at the given index.
+    ///
+    /// Nibbles past the end of the vector are read as zero.
+    fn word_at(&self, idx: usize) -> u64 {
+        let (word, offset) = (idx / WORD_NIBS, (idx % WORD_NIBS) << 2);
+        let hi = self.words.get(word).map_or(0, |&w| w << offset);
+        if offset == 0 {
+            hi
+        } else {
+            hi | self.words.get(word + 1).map_or(0, |&w| w >> (64 - offset))
+        }
+    }
+
+    /// Gets the nibble at the given index.
+    pub fn get<T: u4>(&self, idx: usize) -> Option<T> {
+        if idx < self.len {
+            Some(T::from_lo((self.words[idx / WORD_NIBS] >> nib_shift(idx)) as u8 & 0xF))
+        } else {
+            None
+        }
+    }
+
+    /// Sets the nibble at the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx >= len`.
+    pub fn set<T: u4>(&mut self, idx: usize, nib: T) {
+        assert!(idx < self.len, "index {} out of range for vector of length {}", idx, self.len);
+        let word = &mut self.words[idx / WORD_NIBS];
+        let shift = nib_shift(idx);
+        *word = (*word & !(0xF << shift)) | ((nib.to_lo() as u64) << shift);
+    }
+
+    /// Pushes a nibble onto the end of the vector.
+    pub fn push<T: u4>(&mut self, nib: T) {
+        if self.len % WORD_NIBS == 0 {
+            self.words.push(0);
+        }
+        self.len += 1;
+        let idx = self.len - 1;
+        self.set(idx, nib);
+    }
+
+    /// Removes a nibble from the end of the vector.
+    pub fn pop<T: u4>(&mut self) -> Option<T> {
+        let idx = self.len.checked_sub(1)?;
+        let ret = self.get(idx);
+        self.truncate(idx);
+        ret
+    }
+
+    /// Shortens the vector to the given length, doing nothing if it's already shorter.
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len {
+            self.words.truncate((len + WORD_NIBS - 1) / WORD_NIBS);
+            self.len = len;
+            self.clear_padding();
+        }
+    }
+
+    /// Clears the vector, removing all nibbles.
+    pub fn clear(&mut self) {
+        self.words.clear();
+        self.len = 0;
+    }
+
+    /// Moves every nibble `n` places towards the front, filling the back with zeros.
+    ///
+    /// The first `n` nibbles are discarded, and the length stays the same.
+    pub fn shift_left(&mut self, n: usize) {
+        for i in 0..self.words.len() {
+            self.words[i] = self.word_at(i * WORD_NIBS + n);
+        }
+        self.clear_padding();
+    }
+
+    /// Moves every nibble `n` places towards the back, filling the front with zeros.
+    ///
+    /// The last `n` nibbles are discarded, and the length stays the same.
+    pub fn shift_right(&mut self, n: usize) {
+        let (skip, offset) = (n / WORD_NIBS, (n % WORD_NIBS) << 2);
+        for i in (0..self.words.len()).rev() {
+            let hi = if i >= skip { self.words[i - skip] } else { 0 };
+            let lo = if i > skip { self.words[i - skip - 1] } else { 0 };
+            self.words[i] = if offset == 0 { hi } else { (hi >> offset) | (lo << (64 - offset)) };
+        }
+        self.clear_padding();
+    }
+
+    /// Whether the nibbles of `needle` occur starting at the given index.
+    fn matches_at(&self, needle: &WideNibVec, idx: usize) -> bool {
+        for (k, &word) in needle.words.iter().enumerate() {
+            let i = k * WORD_NIBS;
+            let left = needle.len - i;
+            let mask = if left >= WORD_NIBS { !0 } else { !(!0 >> (left << 2)) };
+            if self.word_at(idx + i) & mask != word {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Finds the first occurrence of `needle`, comparing a word at a time.
+    pub fn find(&self, needle: &WideNibVec) -> Option<usize> {
+        if needle.len > self.len {
+            return None;
+        }
+        (0..self.len - needle.len + 1).find(|&idx| self.matches_at(needle, idx))
+    }
+
+    /// Finds the last occurrence of `needle`, comparing a word at a time.
+    pub fn rfind(&self, needle: &WideNibVec) -> Option<usize> {
+        if needle.len > self.len {
+            return None;
+        }
+        (0..self.len - needle.len + 1).rev().find(|&idx| self.matches_at(needle, idx))
+    }
+
+    /// Whether the vector contains `needle`.
+    pub fn contains(&self, needle: &WideNibVec) -> bool {
+        self.find(needle).is_some()
+    }
+
+    /// Copies the vector into a new, byte-backed `NibVec`.
+    pub fn to_vec(&self) -> NibVec {
+        let mut bytes = Vec::with_capacity(self.words.len() * 8);
+        for word in &self.words {
+            bytes.extend_from_slice(&word.to_be_bytes());
+        }
+        NibVec::from_bytes_with_len(bytes, self.len)
+    }
+}
+impl<'a> From<NibSlice<'a>> for WideNibVec {
+    fn from(slice: NibSlice<'a>) -> Self {
+        WideNibVec::from_nibs(&slice)
+    }
+}
+impl From<NibVec> for WideNibVec {
+    fn from(vec: NibVec) -> Self {
+        WideNibVec::from_nibs(&vec)
+    }
+}
+impl From<WideNibVec> for NibVec {
+    fn from(wide: WideNibVec) -> Self {
+        wide.to_vec()
+    }
+}
+impl PartialEq for WideNibVec {
+    fn eq(&self, rhs: &WideNibVec) -> bool {
+        self.len == rhs.len && self.words == rhs.words
+    }
+}
+impl Eq for WideNibVec {}
+impl PartialOrd for WideNibVec {
+    fn partial_cmp(&self, rhs: &WideNibVec) -> Option<cmp::Ordering> {
+        Some(self.cmp(rhs))
+    }
+}
+impl Ord for WideNibVec {
+    fn cmp(&self, rhs: &WideNibVec) -> cmp::Ordering {
+        // padding is zero, so a tie between words only needs the lengths to break it
+        self.words.cmp(&rhs.words).then(self.len.cmp(&rhs.len))
+    }
+}
+impl hash::Hash for WideNibVec {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.len.hash(state);
+        self.words.hash(state);
+    }
+}
+impl<T: u4> FromIterator<T> for WideNibVec {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut wide = Self::new();
+        wide.extend(iter);
+        wide
+    }
+}
+impl<'a> FromIterator<&'a u4> for WideNibVec {
+    fn from_iter<I: IntoIterator<Item = &'a u4>>(iter: I) -> Self {
+        let mut wide = Self::new();
+        wide.extend(iter);
+        wide
+    }
+}
+impl<T: u4> Extend<T> for WideNibVec {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for nib in iter {
+            self.push(nib);
+        }
+    }
+}
+impl<'a> Extend<&'a u4> for WideNibVec {
+    fn extend<I: IntoIterator<Item = &'a u4>>(&mut self, iter: I) {
+        for nib in iter {
+            self.push(nib.to_u4lo());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base::u4lo;
+    use super::*;
+    use common::XorShift;
+
+    fn nibs(wide: &WideNibVec) -> Vec<u8> {
+        (0..wide.len()).map(|i| wide.get::<u4lo>(i).unwrap().to_lo()).collect()
+    }
+
+    #[test]
+    fn convert() {
+        let vec = NibVec::from_str("0123456789abcdef012").unwrap();
+        let wide = WideNibVec::from(vec.clone());
+        assert_eq!(wide.len(), 19);
+        assert_eq!(wide.as_words(), [0x0123_4567_89ab_cdef, 0x0120_0000_0000_0000]);
+        assert_eq!(wide.to_vec(), vec);
+        let unaligned = WideNibVec::from(vec.slice(1..4));
+        assert_eq!(nibs(&unaligned), [1, 2, 3]);
+        assert_eq!(unaligned.as_words(), [0x1230_0000_0000_0000]);
+    }
+
+    #[test]
+    fn compare() {
+        let a: WideNibVec = WideNibVec::from(NibVec::from_str("12").unwrap());
+        let b: WideNibVec = WideNibVec::from(NibVec::from_str("120").unwrap());
+        let c: WideNibVec = WideNibVec::from(NibVec::from_str("1201").unwrap());
+        assert!(a != b && a < b && b < c);
+        let mut d = c.clone();
+        d.truncate(2);
+        assert_eq!(d, a);
+    }
+
+    #[test]
+    fn model() {
+        let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+        let mut model: Vec<u8> = (0..150).map(|_| rng.nib()).collect();
+        let mut wide: WideNibVec = model.iter().map(|&nib| u4lo::from_lo(nib)).collect();
+        for _ in 0..200 {
+            let len = model.len();
+            match rng.below(5) {
+                0 => {
+                    let n = rng.below(40);
+                    wide.shift_left(n);
+                    model = model.iter().cloned().skip(n).chain(std::iter::repeat(0)).take(len).collect();
+                }
+                1 => {
+                    let n = rng.below(40);
+                    wide.shift_right(n);
+                    model = std::iter::repeat(0).take(n).chain(model.iter().cloned()).take(len).collect();
+                }
+                2 => {
+                    let (start, end) = rng.range(len);
+                    let needle: WideNibVec = model[start..end].iter().map(|&nib| u4lo::from_lo(nib)).collect();
+                    let expected = (0..len - needle.len() + 1)
+                        .find(|&i| model[i..i + needle.len()] == model[start..end]);
+                    assert_eq!(wide.find(&needle), expected);
+                    let expected = (0..len - needle.len() + 1).rev()
+                        .find(|&i| model[i..i + needle.len()] == model[start..end]);
+                    assert_eq!(wide.rfind(&needle), expected);
+                }
+                3 => if len > 0 {
+                    let (idx, nib) = (rng.below(len), rng.nib());
+                    wide.set(idx, u4lo::from_lo(nib));
+                    model[idx] = nib;
+                },
+                _ => {
+                    let nib = rng.nib();
+                    wide.push(u4lo::from_lo(nib));
+                    model.push(nib);
+                }
+            }
+            assert_eq!(nibs(&wide), model);
+            assert_eq!(wide.to_vec(), model.iter().map(|&nib| u4lo::from_lo(nib)).collect::<NibVec>());
+        }
+    }
+}